@@ -11,6 +11,8 @@ fn main() {
     inner::gen_models("index_models");
     inner::gen_models("resizer_models");
     inner::gen_models("users_models");
+    inner::gen_models("api_models");
+    inner::gen_models("revisions_models");
 
     let out_dir = env::var_os("OUT_DIR").unwrap();
 
@@ -0,0 +1,69 @@
+use index::Index;
+use resizer;
+use resizer::{ Resizer, ResizeMode };
+use api_models::{ ComicRepr, ComicListRepr, PanelRepr, ImageRepr };
+
+/// Number of comics in a single page of the list.
+const PAGE_SIZE: usize = 20;
+
+fn image(resizer: &mut Resizer, name: &str, file: &str, mode: ResizeMode) -> Option<ImageRepr> {
+    resizer.get_resized_url(file, mode).map(|i| ImageRepr {
+        name: name.into(),
+        url: ["/ic/", i.relative_url.as_ref()].concat(),
+        width: i.size.w,
+        height: i.size.h,
+    })
+}
+
+fn panel(resizer: &mut Resizer, file: &str) -> PanelRepr {
+    let images = vec![
+        image(resizer, "page", file, resizer::page_mode()),
+        image(resizer, "thumbnail", file, resizer::thumbnail_mode()),
+    ];
+
+    PanelRepr {
+        original_url: ["/i/", file].concat(),
+        images: images.into_iter().filter_map(|i| i).collect(),
+    }
+}
+
+pub fn comic(index: &Index, resizer: &mut Resizer, slug: &str) -> Option<ComicRepr> {
+    let found = match index.find(slug, false) {
+        Some(found) => found,
+        None => return None,
+    };
+
+    Some(ComicRepr {
+        url: found.url(),
+        panels: found.files.iter().map(|file| panel(resizer, file)).collect(),
+        title: found.title,
+        slug: found.slug,
+        prev: found.prev_slug,
+        next: found.next_slug,
+        first: found.first_slug,
+        last: found.last_slug,
+    })
+}
+
+/// Returns requested page of published comics, oldest first. Pages start at 1.
+pub fn list(index: &Index, resizer: &mut Resizer, page: usize) -> Option<ComicListRepr> {
+    let entries = index.entries(false);
+    let pages = (entries.len() + PAGE_SIZE - 1) / PAGE_SIZE;
+
+    if page == 0 || (page > pages && page != 1) {
+        return None;
+    }
+
+    let comics = entries.iter()
+        .skip((page - 1) * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .filter_map(|entry| comic(index, resizer, &entry.slug))
+        .collect();
+
+    Some(ComicListRepr {
+        page: page,
+        pages: pages,
+        total: entries.len(),
+        comics: comics,
+    })
+}
@@ -0,0 +1,102 @@
+use time;
+
+use index::{ Index, Entry };
+use resizer;
+use resizer::Resizer;
+use globals::Globals;
+use template::{ escape, partial };
+
+/// Heading the entry is listed under: its chapter, or the month it was
+/// published in. Entries without either are listed without heading.
+fn group_of(entry: &Entry) -> Option<String> {
+    match entry.chapter {
+        Some(ref chapter) => Some(chapter.clone()),
+        None => match entry.date {
+            Some(date) => match time::at_utc(date).strftime("%B %Y") {
+                Ok(month) => Some(format!("{}", month)),
+                Err(_) => None,
+            },
+            None => None,
+        },
+    }
+}
+
+fn render_entry(entry: &Entry, resizer: &mut Resizer) -> String {
+    let thumbnail = match resizer.get_resized_url(&entry.file, resizer::thumbnail_mode()) {
+        Some(i) => partial("views/archive_thumbnail.html", &Globals::new()
+            .with("src", escape(&i.relative_url))
+            .with("width", i.size.w.to_string())
+            .with("height", i.size.h.to_string())),
+        None => String::new(),
+    };
+
+    partial("views/archive_entry.html", &Globals::new()
+        .with("url", escape(&entry.url()))
+        .with("thumbnail", thumbnail)
+        .with("title", escape(&entry.title)))
+}
+
+/// Renders plain list of entries with thumbnails.
+pub fn render_list(entries: &[Entry], resizer: &mut Resizer) -> String {
+    let items = entries.iter()
+        .map(|entry| render_entry(entry, resizer))
+        .collect::<Vec<_>>();
+
+    partial("views/archive_list.html", &Globals::new()
+        .with("entries", items.concat()))
+}
+
+/// Renders entries listed under the heading, or a plain list without one.
+fn render_group(heading: &Option<String>, entries: &[String]) -> String {
+    match *heading {
+        Some(ref heading) => partial("views/archive_group.html", &Globals::new()
+            .with("heading", escape(heading))
+            .with("entries", entries.concat())),
+        None => partial("views/archive_list.html", &Globals::new()
+            .with("entries", entries.concat())),
+    }
+}
+
+/// Renders list of all visible pages with thumbnails, grouped by series and
+/// then by chapter or publication month.
+pub fn render(index: &Index, resizer: &mut Resizer, preview: bool) -> String {
+    let entries = index.entries(preview);
+
+    let mut series: Vec<Option<String>> = Vec::new();
+    for entry in &entries {
+        if !series.contains(&entry.series) {
+            series.push(entry.series.clone());
+        }
+    }
+
+    let mut html = String::new();
+
+    for current in &series {
+        if let Some(ref name) = *current {
+            html.push_str(&partial("views/archive_series.html", &Globals::new()
+                .with("name", escape(name))));
+        }
+
+        // consecutive entries with the same heading form a group
+        let mut groups: Vec<(Option<String>, Vec<String>)> = Vec::new();
+        for entry in entries.iter().filter(|e| e.series == *current) {
+            let entry_group = group_of(entry);
+            let rendered = render_entry(entry, resizer);
+            let same = match groups.last() {
+                Some(&(ref heading, _)) => *heading == entry_group,
+                None => false,
+            };
+            if same {
+                groups.last_mut().unwrap().1.push(rendered);
+            } else {
+                groups.push((entry_group, vec![rendered]));
+            }
+        }
+
+        for &(ref heading, ref items) in &groups {
+            html.push_str(&render_group(heading, items));
+        }
+    }
+
+    html
+}
@@ -0,0 +1,85 @@
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+use serde_json;
+
+use index_models::IndexRepr;
+use backend::Backend;
+use errors;
+use errors::LoadError;
+
+/// All records in a single JSON array file, rewritten on every change.
+pub struct JsonBackend {
+    path: PathBuf,
+}
+
+impl JsonBackend {
+    pub fn new(path: &Path) -> JsonBackend {
+        JsonBackend {
+            path: path.into(),
+        }
+    }
+
+    /// Writes items to a temporary file next to the index and renames it over
+    /// the original, so the index file is never left half-written.
+    fn write(&self, items: &[IndexRepr]) -> Result<(), LoadError> {
+        let serialized = match serde_json::to_string_pretty(&items) {
+            Ok(serialized) => serialized,
+            Err(e) => return Err(LoadError::Invalid {
+                path: self.path.clone(),
+                message: format!("error serializing index storage {:?}", e),
+            }),
+        };
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        let written = File::create(&tmp_path)
+            .and_then(|mut f| f.write_all(serialized.as_bytes()).and_then(|_| f.sync_all()))
+            .and_then(|_| fs::rename(&tmp_path, &self.path));
+
+        match written {
+            Ok(_) => Ok(()),
+            Err(e) => Err(LoadError::Write { path: self.path.clone(), error: e }),
+        }
+    }
+}
+
+impl Backend for JsonBackend {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Missing file is an empty index.
+    fn list(&self) -> Result<Vec<IndexRepr>, LoadError> {
+        if let Err(_) = fs::metadata(&self.path) {
+            println!("failed to open index storage {:?}, starting empty", self.path);
+            return Ok(Vec::new());
+        }
+
+        errors::read_json(&self.path)
+    }
+
+    fn insert(&mut self, item: &IndexRepr) -> Result<(), LoadError> {
+        let mut items = try!(self.list());
+        items.push(item.clone());
+        self.write(&items)
+    }
+
+    fn update(&mut self, slug: &str, item: &IndexRepr) -> Result<(), LoadError> {
+        let mut items = try!(self.list());
+        match items.iter().position(|i| i.slug == slug) {
+            Some(position) => items[position] = item.clone(),
+            None => return Err(LoadError::Invalid {
+                path: self.path.clone(),
+                message: format!("slug {:?} is not in index storage", slug),
+            }),
+        }
+        self.write(&items)
+    }
+
+    fn delete(&mut self, slug: &str) -> Result<(), LoadError> {
+        let mut items = try!(self.list());
+        items.retain(|i| i.slug != slug);
+        self.write(&items)
+    }
+}
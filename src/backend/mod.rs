@@ -0,0 +1,48 @@
+use std::path::Path;
+
+use index_models::IndexRepr;
+use errors::LoadError;
+
+pub mod json;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// Persistent storage of index records, kept in the order pages were added.
+pub trait Backend: Send {
+    /// File the records are stored in, watched for outside changes.
+    fn path(&self) -> &Path;
+
+    /// Returns all records in order.
+    fn list(&self) -> Result<Vec<IndexRepr>, LoadError>;
+
+    /// Appends record after all others.
+    fn insert(&mut self, item: &IndexRepr) -> Result<(), LoadError>;
+
+    /// Replaces record with the given slug, keeping its position.
+    fn update(&mut self, slug: &str, item: &IndexRepr) -> Result<(), LoadError>;
+
+    fn delete(&mut self, slug: &str) -> Result<(), LoadError>;
+}
+
+/// Opens backend based on file extension: `.sqlite` and `.db` files use
+/// SQLite database when built with `sqlite` feature, anything else is JSON.
+pub fn open(path: &Path) -> Result<Box<Backend>, LoadError> {
+    match path.extension().map(|ext| ext.to_string_lossy().into_owned()) {
+        Some(ref ext) if ext == "sqlite" || ext == "db" => open_sqlite(path),
+        _ => Ok(Box::new(json::JsonBackend::new(path))),
+    }
+}
+
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: &Path) -> Result<Box<Backend>, LoadError> {
+    let backend = try!(sqlite::SqliteBackend::open(path));
+    Ok(Box::new(backend))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite(path: &Path) -> Result<Box<Backend>, LoadError> {
+    Err(LoadError::Database {
+        path: path.into(),
+        message: "built without sqlite feature".into(),
+    })
+}
@@ -0,0 +1,100 @@
+use std::path::{ Path, PathBuf };
+use rusqlite::Connection;
+use serde_json;
+
+use index_models::IndexRepr;
+use backend::Backend;
+use errors::LoadError;
+
+/// Records in an embedded SQLite database, one row per page. Rows keep the
+/// serialized record, so new fields need no schema changes.
+pub struct SqliteBackend {
+    path: PathBuf,
+    connection: Connection,
+}
+
+fn database_error(path: &Path, message: String) -> LoadError {
+    LoadError::Database { path: path.into(), message: message }
+}
+
+impl SqliteBackend {
+    pub fn open(path: &Path) -> Result<SqliteBackend, LoadError> {
+        let connection = try!(Connection::open(path)
+            .map_err(|e| database_error(path, format!("failed to open: {:?}", e))));
+
+        try!(connection.execute(
+            "CREATE TABLE IF NOT EXISTS pages (
+                position INTEGER PRIMARY KEY AUTOINCREMENT,
+                slug TEXT NOT NULL UNIQUE,
+                data TEXT NOT NULL
+            )",
+            &[]
+        ).map_err(|e| database_error(path, format!("failed to create index table: {:?}", e))));
+
+        Ok(SqliteBackend {
+            path: path.into(),
+            connection: connection,
+        })
+    }
+
+    fn serialize(&self, item: &IndexRepr) -> Result<String, LoadError> {
+        serde_json::to_string(item).map_err(|e| LoadError::Invalid {
+            path: self.path.clone(),
+            message: format!("error serializing {:?}: {:?}", item.slug, e),
+        })
+    }
+
+    fn query(&self, sql: &str) -> Result<Vec<IndexRepr>, LoadError> {
+        let mut statement = try!(self.connection.prepare(sql)
+            .map_err(|e| database_error(&self.path, format!("error preparing query: {:?}", e))));
+
+        let rows: Vec<String> = {
+            let mapped = try!(statement.query_map(&[], |row| row.get(0))
+                .map_err(|e| database_error(&self.path, format!("error querying: {:?}", e))));
+            try!(mapped.collect::<Result<Vec<String>, _>>()
+                .map_err(|e| database_error(&self.path, format!("error reading rows: {:?}", e))))
+        };
+
+        rows.iter()
+            .map(|data| serde_json::from_str(data).map_err(|e| LoadError::from_json(&self.path, e)))
+            .collect()
+    }
+}
+
+impl Backend for SqliteBackend {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn list(&self) -> Result<Vec<IndexRepr>, LoadError> {
+        self.query("SELECT data FROM pages ORDER BY position")
+    }
+
+    fn insert(&mut self, item: &IndexRepr) -> Result<(), LoadError> {
+        let data = try!(self.serialize(item));
+        self.connection.execute(
+            "INSERT INTO pages (slug, data) VALUES (?, ?)",
+            &[&item.slug, &data]
+        )
+            .map(|_| ())
+            .map_err(|e| database_error(&self.path, format!("error inserting {:?}: {:?}", item.slug, e)))
+    }
+
+    fn update(&mut self, slug: &str, item: &IndexRepr) -> Result<(), LoadError> {
+        let data = try!(self.serialize(item));
+        match self.connection.execute(
+            "UPDATE pages SET slug = ?, data = ? WHERE slug = ?",
+            &[&item.slug, &data, &slug]
+        ) {
+            Ok(0) => Err(database_error(&self.path, format!("slug {:?} is not in index database", slug))),
+            Ok(_) => Ok(()),
+            Err(e) => Err(database_error(&self.path, format!("error updating {:?}: {:?}", slug, e))),
+        }
+    }
+
+    fn delete(&mut self, slug: &str) -> Result<(), LoadError> {
+        self.connection.execute("DELETE FROM pages WHERE slug = ?", &[&slug])
+            .map(|_| ())
+            .map_err(|e| database_error(&self.path, format!("error deleting {:?}: {:?}", slug, e)))
+    }
+}
@@ -0,0 +1,117 @@
+use time;
+
+use index;
+use index::{ Index, Entry };
+use globals::Globals;
+use template::{ escape, partial };
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 => if is_leap_year(year) { 29 } else { 28 },
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Year and month (1 to 12) the entry was published in.
+fn month_of(entry: &Entry) -> Option<(i32, u32)> {
+    entry.date.map(|date| {
+        let tm = time::at_utc(date);
+        (tm.tm_year + 1900, (tm.tm_mon + 1) as u32)
+    })
+}
+
+pub fn calendar_url(year: i32, month: u32) -> String {
+    format!("/calendar/{:04}/{:02}", year, month)
+}
+
+/// Returns the month of the latest dated page.
+pub fn latest_month(index: &Index, preview: bool) -> Option<(i32, u32)> {
+    index.entries(preview).iter().filter_map(month_of).max()
+}
+
+/// Renders month title, links to neighbouring months with pages and a table
+/// of days linking to pages published on them. Returns `None` for invalid
+/// months.
+pub fn render(index: &Index, year: i32, month: u32, preview: bool) -> Option<(String, String)> {
+    if month < 1 || month > 12 {
+        return None;
+    }
+
+    let first_day = match time::strptime(&format!("{:04}-{:02}-01", year, month), "%Y-%m-%d") {
+        Ok(tm) => time::at_utc(tm.to_timespec()),
+        Err(_) => return None,
+    };
+    let title = match first_day.strftime("%B %Y") {
+        Ok(title) => format!("{}", title),
+        Err(_) => return None,
+    };
+
+    let entries = index.entries(preview);
+    let mut months: Vec<(i32, u32)> = entries.iter().filter_map(month_of).collect();
+    months.sort();
+    months.dedup();
+
+    let mut days: Vec<Vec<&Entry>> = (0..days_in_month(year, month)).map(|_| Vec::new()).collect();
+    for entry in &entries {
+        if month_of(entry) == Some((year, month)) {
+            let day = time::at_utc(entry.date.unwrap()).tm_mday as usize;
+            days[day - 1].push(entry);
+        }
+    }
+
+    let prev = match months.iter().rev().find(|&&m| m < (year, month)) {
+        Some(&(y, m)) => partial("views/calendar_prev.html", &Globals::new()
+            .with("href", calendar_url(y, m))),
+        None => String::new(),
+    };
+    let next = match months.iter().find(|&&m| m > (year, month)) {
+        Some(&(y, m)) => partial("views/calendar_next.html", &Globals::new()
+            .with("href", calendar_url(y, m))),
+        None => String::new(),
+    };
+
+    let empty_day = || partial("views/calendar_day.html", &Globals::new().with("day", String::new()));
+
+    // weeks start on Monday, tm_wday counts from Sunday
+    let offset = ((first_day.tm_wday + 6) % 7) as usize;
+    let mut cells: Vec<String> = (0..offset).map(|_| empty_day()).collect();
+
+    for (i, pages) in days.iter().enumerate() {
+        if pages.len() == 0 {
+            cells.push(partial("views/calendar_day.html", &Globals::new()
+                .with("day", (i + 1).to_string())));
+            continue;
+        }
+
+        let links = pages.iter()
+            .map(|entry| partial("views/calendar_page.html", &Globals::new()
+                .with("url", escape(&entry.url()))
+                .with("title", escape(&entry.title))))
+            .collect::<Vec<_>>();
+        cells.push(partial("views/calendar_published_day.html", &Globals::new()
+            .with("href", index::date_url(pages[0].date.unwrap()))
+            .with("day", (i + 1).to_string())
+            .with("pages", links.concat())));
+    }
+
+    while cells.len() % 7 != 0 {
+        cells.push(empty_day());
+    }
+
+    let weeks = cells.chunks(7)
+        .map(|days| partial("views/calendar_week.html", &Globals::new()
+            .with("days", days.concat())))
+        .collect::<Vec<_>>();
+
+    let html = partial("views/calendar_month.html", &Globals::new()
+        .with("prev", prev)
+        .with("next", next)
+        .with("weeks", weeks.concat()));
+
+    Some((title, html))
+}
@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::path::Path;
+use image;
+
+use backend;
+use index;
+
+/// Validates index records and their images, prints a report and returns
+/// `true` if no problems were found.
+pub fn run(index_path: &Path, images_path: &Path) -> bool {
+    // backends treat missing index as empty, which is fine for a new site
+    // but here means the wrong path is being checked
+    if !index_path.exists() {
+        println!("index {:?} does not exist", index_path);
+        return false;
+    }
+
+    let items = match backend::open(index_path).and_then(|b| b.list()) {
+        Ok(items) => items,
+        Err(e) => {
+            println!("{}", e);
+            return false;
+        }
+    };
+
+    let mut problems: Vec<String> = Vec::new();
+    let mut owners: HashMap<String, String> = HashMap::new();
+
+    for item in &items {
+        let mut names = vec![item.slug.clone()];
+        if let Some(ref aliases) = item.aliases {
+            names.extend(aliases.iter().cloned());
+        }
+
+        for name in names {
+            if !index::is_valid_slug(&name) {
+                problems.push(format!("{:?}: slug {:?} is not URL safe", item.slug, name));
+            }
+            match owners.get(&name) {
+                Some(owner) => problems.push(format!("{:?}: slug {:?} is already used by {:?}", item.slug, name, owner)),
+                None => (),
+            }
+            owners.insert(name, item.slug.clone());
+        }
+
+        if let Some(ref series) = item.series {
+            if !index::is_valid_slug(series) {
+                problems.push(format!("{:?}: series {:?} is not URL safe", item.slug, series));
+            }
+        }
+
+        if let Some(ref publish_at) = item.publish_at {
+            if let None = index::parse_time(publish_at) {
+                problems.push(format!("{:?}: invalid publish_at {:?}", item.slug, publish_at));
+            }
+        }
+
+        if let Some(ref date) = item.date {
+            if let None = index::parse_date(date) {
+                problems.push(format!("{:?}: invalid date {:?}", item.slug, date));
+            }
+        }
+
+        let mut files = vec![item.file.clone()];
+        if let Some(ref panels) = item.panels {
+            files.extend(panels.iter().cloned());
+        }
+
+        for file in files {
+            if let Err(e) = image::open(images_path.join(&file)) {
+                problems.push(format!("{:?}: image {:?} can not be opened: {:?}", item.slug, file, e));
+            }
+        }
+    }
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    println!("checked {} pages in {:?}, {} problems found", items.len(), index_path, problems.len());
+
+    problems.len() == 0
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{ Path, PathBuf };
+use std::time::SystemTime;
+use pulldown_cmark::{ Parser, Event, Tag, html };
+
+#[derive(PartialEq)]
+enum Source {
+    Inline(String),
+    Sidecar(SystemTime),
+}
+
+/// Renders author commentary written in Markdown, either given in the index
+/// or in a `.md` file next to the page image, and keeps rendered HTML until
+/// the source changes.
+pub struct Commentary {
+    root_path: PathBuf,
+    rendered: HashMap<String, (Source, String)>,
+}
+
+fn is_safe_url(url: &str) -> bool {
+    match url.find(':') {
+        Some(pos) => {
+            let scheme = url[..pos].to_lowercase();
+            match scheme.as_ref() {
+                "http" | "https" | "mailto" => true,
+                _ => url[..pos].contains('/') || url[..pos].contains('?') || url[..pos].contains('#'),
+            }
+        },
+        None => true,
+    }
+}
+
+/// Converts Markdown to HTML. Raw HTML in the source is escaped and links
+/// with unexpected schemes are dropped.
+pub fn render(markdown: &str) -> String {
+    let events = Parser::new(markdown).map(|event| match event {
+        Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+        Event::Start(Tag::Link(ref url, ref title)) if !is_safe_url(url) => {
+            Event::Start(Tag::Link("".into(), title.clone()))
+        },
+        Event::Start(Tag::Image(ref url, ref title)) if !is_safe_url(url) => {
+            Event::Start(Tag::Image("".into(), title.clone()))
+        },
+        other => other,
+    });
+
+    let mut result = String::new();
+    html::push_html(&mut result, events);
+    result
+}
+
+impl Commentary {
+    pub fn new(root_path: &Path) -> Commentary {
+        Commentary {
+            root_path: root_path.into(),
+            rendered: HashMap::new(),
+        }
+    }
+
+    fn sidecar_path(&self, file: &str) -> PathBuf {
+        self.root_path.join(file).with_extension("md")
+    }
+
+    fn read_sidecar(path: &Path) -> Option<String> {
+        let mut f = match File::open(path) {
+            Ok(f) => f,
+            _ => return None,
+        };
+
+        let mut contents = String::new();
+        if let Err(e) = f.read_to_string(&mut contents) {
+            println!("error reading commentary {:?}: {:?}", path, e);
+            return None;
+        }
+
+        Some(contents)
+    }
+
+    /// Returns rendered commentary for the page image. Commentary from the
+    /// index takes priority over the sidecar file.
+    pub fn get(&mut self, file: &str, inline: Option<&str>) -> Option<String> {
+        let source = match inline {
+            Some(markdown) => Source::Inline(markdown.into()),
+            None => {
+                let path = self.sidecar_path(file);
+                match fs::metadata(&path).and_then(|md| md.modified()) {
+                    Ok(modified) => Source::Sidecar(modified),
+                    Err(_) => {
+                        self.rendered.remove(file);
+                        return None;
+                    },
+                }
+            },
+        };
+
+        if let Some(&(ref cached_source, ref html)) = self.rendered.get(file) {
+            if *cached_source == source {
+                return Some(html.clone());
+            }
+        }
+
+        let markdown = match source {
+            Source::Inline(ref markdown) => markdown.clone(),
+            Source::Sidecar(_) => match Commentary::read_sidecar(&self.sidecar_path(file)) {
+                Some(markdown) => markdown,
+                None => return None,
+            },
+        };
+
+        let html = render(&markdown);
+        self.rendered.insert(file.into(), (source, html.clone()));
+
+        Some(html)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ render, is_safe_url };
+
+    #[test]
+    fn escapes_raw_html() {
+        let html = render("<script>alert(1)</script>\n\nafter");
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn escapes_inline_html() {
+        let html = render("hello <b onclick=\"steal()\">there</b>");
+        assert!(!html.contains("<b"));
+        assert!(html.contains("&lt;b"));
+    }
+
+    #[test]
+    fn rejects_script_urls() {
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url("JaVaScRiPt:alert(1)"));
+        assert!(!is_safe_url("data:text/html;base64,PHNjcmlwdD4="));
+        assert!(!is_safe_url("vbscript:msgbox"));
+    }
+
+    #[test]
+    fn accepts_web_and_relative_urls() {
+        assert!(is_safe_url("http://example.com/"));
+        assert!(is_safe_url("HTTPS://example.com/"));
+        assert!(is_safe_url("mailto:author@example.com"));
+        assert!(is_safe_url("/c/page"));
+        assert!(is_safe_url("page"));
+        assert!(is_safe_url("#notes"));
+        assert!(is_safe_url("/search?q=a:b"));
+        assert!(is_safe_url("page?from=a:b"));
+    }
+
+    #[test]
+    fn drops_unsafe_links() {
+        for markdown in &[
+            "[link](javascript:alert(1))",
+            "[link](JaVaScRiPt:alert(1))",
+            "[link](data:text/html;base64,PHNjcmlwdD4=)",
+            "![image](javascript:alert(1))",
+        ] {
+            let html = render(markdown);
+            assert!(!html.to_lowercase().contains("javascript"), "{}", html);
+            assert!(!html.contains("data:"), "{}", html);
+        }
+    }
+
+    #[test]
+    fn keeps_safe_links() {
+        assert!(render("[link](/c/page)").contains("href=\"/c/page\""));
+        assert!(render("[link](page)").contains("href=\"page\""));
+        assert!(render("[link](http://example.com/)").contains("href=\"http://example.com/\""));
+        assert!(render("[link](https://example.com/)").contains("href=\"https://example.com/\""));
+    }
+}
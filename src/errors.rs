@@ -0,0 +1,93 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{ Path, PathBuf };
+use serde;
+use serde_json;
+
+/// Failure to load or store a data file, with enough detail to find the
+/// problem.
+#[derive(Debug)]
+pub enum LoadError {
+    /// File could not be opened or read.
+    Io { path: PathBuf, error: io::Error },
+    /// File could not be written.
+    Write { path: PathBuf, error: io::Error },
+    /// File is not valid JSON or does not match the expected structure.
+    Syntax { path: PathBuf, line: usize, column: usize, message: String },
+    /// Contents are well formed, but can not be used.
+    Invalid { path: PathBuf, message: String },
+    /// Database could not be opened or queried.
+    Database { path: PathBuf, message: String },
+}
+
+impl LoadError {
+    pub fn from_json(path: &Path, error: serde_json::Error) -> LoadError {
+        match error {
+            serde_json::Error::SyntaxError(code, line, column) => LoadError::Syntax {
+                path: path.into(),
+                line: line,
+                column: column,
+                message: format!("{:?}", code),
+            },
+            serde_json::Error::IoError(error) => LoadError::Io {
+                path: path.into(),
+                error: error,
+            },
+            other => LoadError::Syntax {
+                path: path.into(),
+                line: 0,
+                column: 0,
+                message: format!("{}", other),
+            },
+        }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LoadError::Io { ref path, ref error } =>
+                write!(f, "failed to read {}: {}", path.display(), error),
+            LoadError::Write { ref path, ref error } =>
+                write!(f, "failed to write {}: {}", path.display(), error),
+            LoadError::Syntax { ref path, line: 0, column: 0, ref message } =>
+                write!(f, "failed to parse {}: {}", path.display(), message),
+            LoadError::Syntax { ref path, line, column, ref message } =>
+                write!(f, "failed to parse {} at line {}, column {}: {}", path.display(), line, column, message),
+            LoadError::Invalid { ref path, ref message } =>
+                write!(f, "invalid data in {}: {}", path.display(), message),
+            LoadError::Database { ref path, ref message } =>
+                write!(f, "database error in {}: {}", path.display(), message),
+        }
+    }
+}
+
+impl Error for LoadError {
+    fn description(&self) -> &str {
+        match *self {
+            LoadError::Io { .. } => "failed to read file",
+            LoadError::Write { .. } => "failed to write file",
+            LoadError::Syntax { .. } => "failed to parse file",
+            LoadError::Invalid { .. } => "invalid data in file",
+            LoadError::Database { .. } => "database error",
+        }
+    }
+}
+
+/// Reads and deserializes JSON file.
+pub fn read_json<T: serde::Deserialize>(path: &Path) -> Result<T, LoadError> {
+    let mut f = match File::open(path) {
+        Ok(f) => f,
+        Err(e) => return Err(LoadError::Io { path: path.into(), error: e }),
+    };
+
+    let mut contents = String::new();
+    if let Err(e) = f.read_to_string(&mut contents) {
+        return Err(LoadError::Io { path: path.into(), error: e });
+    }
+
+    serde_json::from_str(&contents).map_err(|e| LoadError::from_json(path, e))
+}
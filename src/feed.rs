@@ -0,0 +1,116 @@
+use time;
+use time::Timespec;
+
+use index::{ Index, Entry };
+use resizer;
+use resizer::Resizer;
+use template::escape;
+
+/// Number of latest pages included in feeds.
+const FEED_LENGTH: usize = 20;
+
+struct FeedEntry {
+    title: String,
+    link: String,
+    content: String,
+    published: Option<Timespec>,
+}
+
+fn latest(index: &Index, resizer: &mut Resizer, base_url: &str) -> Vec<FeedEntry> {
+    let entries = index.entries(false);
+    let skip = if entries.len() > FEED_LENGTH { entries.len() - FEED_LENGTH } else { 0 };
+
+    entries.iter()
+        .skip(skip)
+        .rev()
+        .map(|entry: &Entry| {
+            let link = [base_url, entry.url().as_ref()].concat();
+            let content = entry.files.iter()
+                .filter_map(|file| resizer.get_resized_url(file, resizer::page_mode()))
+                .map(|i| format!(
+                    "<img src=\"{}/ic/{}\" width=\"{}\" height=\"{}\" alt=\"{}\" />",
+                    base_url, i.relative_url, i.size.w, i.size.h, escape(&entry.title)
+                ))
+                .collect::<Vec<_>>()
+                .concat();
+
+            FeedEntry {
+                title: entry.title.clone(),
+                link: link,
+                content: content,
+                published: entry.date,
+            }
+        })
+        .collect()
+}
+
+fn rfc822(value: Timespec) -> String {
+    match time::at_utc(value).strftime("%a, %d %b %Y %H:%M:%S GMT") {
+        Ok(formatted) => format!("{}", formatted),
+        Err(_) => String::new(),
+    }
+}
+
+fn rfc3339(value: Timespec) -> String {
+    format!("{}", time::at_utc(value).rfc3339())
+}
+
+/// Builds RSS 2.0 feed of the latest published pages. It is generated from the
+/// current index on every request, so reloads and edits show up immediately.
+pub fn rss(index: &Index, resizer: &mut Resizer, base_url: &str) -> String {
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\"><channel>");
+    xml.push_str("<title>Comics</title>");
+    xml.push_str(&format!("<link>{}/</link>", escape(base_url)));
+    xml.push_str("<description>Latest comics</description>");
+
+    for entry in latest(index, resizer, base_url) {
+        xml.push_str("<item>");
+        xml.push_str(&format!("<title>{}</title>", escape(&entry.title)));
+        xml.push_str(&format!("<link>{}</link>", escape(&entry.link)));
+        xml.push_str(&format!("<guid isPermaLink=\"true\">{}</guid>", escape(&entry.link)));
+        xml.push_str(&format!("<description>{}</description>", escape(&entry.content)));
+        if let Some(published) = entry.published {
+            xml.push_str(&format!("<pubDate>{}</pubDate>", rfc822(published)));
+        }
+        xml.push_str("</item>");
+    }
+
+    xml.push_str("</channel></rss>");
+    xml
+}
+
+/// Builds Atom feed of the latest published pages.
+pub fn atom(index: &Index, resizer: &mut Resizer, base_url: &str) -> String {
+    let entries = latest(index, resizer, base_url);
+    let updated = entries.iter()
+        .filter_map(|entry| entry.published)
+        .max()
+        .unwrap_or(time::get_time());
+
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">");
+    xml.push_str("<title>Comics</title>");
+    xml.push_str(&format!("<id>{}/</id>", escape(base_url)));
+    xml.push_str(&format!("<link href=\"{}/\" />", escape(base_url)));
+    xml.push_str(&format!("<link rel=\"self\" href=\"{}/feed.atom\" />", escape(base_url)));
+    xml.push_str(&format!("<updated>{}</updated>", rfc3339(updated)));
+    xml.push_str("<author><name>Comics</name></author>");
+
+    for entry in entries {
+        xml.push_str("<entry>");
+        xml.push_str(&format!("<title>{}</title>", escape(&entry.title)));
+        xml.push_str(&format!("<id>{}</id>", escape(&entry.link)));
+        xml.push_str(&format!("<link href=\"{}\" />", escape(&entry.link)));
+        xml.push_str(&format!("<updated>{}</updated>", rfc3339(entry.published.unwrap_or(updated))));
+        xml.push_str(&format!("<content type=\"html\">{}</content>", escape(&entry.content)));
+        xml.push_str("</entry>");
+    }
+
+    xml.push_str("</feed>");
+    xml
+}
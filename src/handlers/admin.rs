@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+use iron::prelude::*;
+use iron::status;
+use iron::method;
+use hyper::header::SetCookie;
+use cookie::Cookie as CookiePair;
+use image;
+use time;
+use url;
+
+use index;
+use index_models::IndexRepr;
+use iron_ex;
+use revisions;
+use globals::Globals;
+use template;
+use handlers::{ App, Session, with_index, with_index_and_resizer, not_found, redirect, view,
+    route_param, query_param, form_param, session_csrf, session_user_with_csrf, session_duration,
+    check_admin };
+use random_str;
+
+pub fn upload_form(app: &App, req: &mut Request) -> IronResult<Response> {
+    let csrf = match session_csrf(app, req) {
+        Some(csrf) => csrf,
+        None => return Ok(not_found()),
+    };
+
+    let mut vals = Globals::new()
+        .with("csrf", csrf);
+
+    vals.amend("message", match req.url.query {
+        Some(_) => "<span class=\"error\">Enter title, unique slug and an image.</span>".into(),
+        None => "".into(),
+    });
+
+    Ok(view("views/admin/upload.html", vals))
+}
+
+/// Largest accepted upload form, image included.
+const MAX_UPLOAD_SIZE: u64 = 20 * 1024 * 1024;
+
+pub fn upload(app: &App, req: &mut Request) -> IronResult<Response> {
+    if !check_admin(app, req) {
+        return Ok(not_found());
+    }
+
+    let invalid = redirect("/admin/upload?invalid".into(), status::SeeOther);
+
+    // reading and storing the image does not need the index, so a slow
+    // upload does not hold up other requests
+    let parts = match iron_ex::multipart::parse(req, MAX_UPLOAD_SIZE) {
+        Some(parts) => parts,
+        None => return Ok(invalid),
+    };
+
+    let user = match session_user_with_csrf(app, req, parts.get("csrf").map(|part| part.text())) {
+        Some(user) => user,
+        None => return Ok(not_found()),
+    };
+
+    let (repr, path) = match save_upload(&parts) {
+        Some(upload) => upload,
+        None => return Ok(invalid),
+    };
+
+    with_index(app, |index| {
+        let slug = repr.slug.clone();
+        if index.contains(&slug) {
+            let _ = fs::remove_file(&path);
+            return Ok(invalid);
+        }
+
+        if let Err(e) = index.push(repr, &user) {
+            println!("Error adding uploaded page: {}", e);
+            let _ = fs::remove_file(&path);
+            return Ok(invalid);
+        }
+
+        Ok(redirect(index.permalink(&slug), status::SeeOther))
+    })
+}
+
+fn upload_extension(filename: &str) -> Option<String> {
+    match Path::new(filename).extension() {
+        Some(ext) => {
+            let ext = ext.to_string_lossy().to_lowercase();
+            match ext.as_ref() {
+                "png" | "jpg" | "jpeg" | "gif" => Some(ext.clone()),
+                _ => None,
+            }
+        },
+        None => None,
+    }
+}
+
+fn optional_text(parts: &HashMap<String, iron_ex::multipart::Part>, key: &str) -> Option<String> {
+    match parts.get(key) {
+        Some(part) if part.text().trim().len() > 0 => Some(part.text().trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Validates upload form and writes the image into `data/images`. Returns
+/// the page to add and the path of the written image.
+fn save_upload(parts: &HashMap<String, iron_ex::multipart::Part>) -> Option<(IndexRepr, PathBuf)> {
+    let title = match parts.get("title") {
+        Some(part) => part.text().trim().to_string(),
+        None => String::new(),
+    };
+    let slug = match parts.get("slug") {
+        Some(part) => part.text().trim().to_string(),
+        None => String::new(),
+    };
+
+    let series = match parts.get("series") {
+        Some(part) if part.text().trim().len() > 0 => {
+            let value = part.text().trim().to_string();
+            if !index::is_valid_slug(&value) {
+                return None;
+            }
+            Some(value)
+        },
+        _ => None,
+    };
+    let chapter = optional_text(parts, "chapter");
+
+    let tags = match parts.get("tags") {
+        Some(part) => {
+            let tags: Vec<String> = part.text().split(',')
+                .map(|tag| tag.trim().to_string())
+                .filter(|tag| tag.len() > 0)
+                .collect();
+            if tags.len() > 0 { Some(tags) } else { None }
+        },
+        None => None,
+    };
+
+    let publish_at = match parts.get("publish_at") {
+        Some(part) if part.text().trim().len() > 0 => {
+            let value = part.text().trim().to_string();
+            match index::parse_time(&value) {
+                Some(_) => Some(value),
+                None => return None,
+            }
+        },
+        _ => None,
+    };
+
+    let date = match parts.get("date") {
+        Some(part) if part.text().trim().len() > 0 => {
+            let value = part.text().trim().to_string();
+            match index::parse_date(&value) {
+                Some(_) => Some(value),
+                None => return None,
+            }
+        },
+        _ => None,
+    };
+
+    if title.len() == 0 || !index::is_valid_slug(&slug) {
+        return None;
+    }
+
+    let (extension, data) = match parts.get("image") {
+        Some(&iron_ex::multipart::Part { filename: Some(ref filename), ref data }) if data.len() > 0 => {
+            match upload_extension(filename) {
+                Some(ext) => (ext, data),
+                None => return None,
+            }
+        },
+        _ => return None,
+    };
+
+    if let Err(e) = image::load_from_memory(data) {
+        println!("uploaded file is not an image: {:?}", e);
+        return None;
+    }
+
+    // images are served as is from `/i/`, random part keeps the image of a
+    // scheduled page from being found before the page is published
+    let file = [slug.as_ref(), "-", random_str(16).as_ref(), ".", extension.as_ref()].concat();
+    let path = Path::new("data/images").join(&file);
+
+    if let Ok(_) = fs::metadata(&path) {
+        println!("upload target already exists {:?}", path);
+        return None;
+    }
+
+    match File::create(&path) {
+        Ok(mut f) => if let Err(e) = f.write_all(data) {
+            println!("error writing upload {:?}: {:?}", path, e);
+            let _ = fs::remove_file(&path);
+            return None;
+        },
+        Err(e) => {
+            println!("error creating upload {:?}: {:?}", path, e);
+            return None;
+        },
+    }
+
+    Some((IndexRepr {
+        title: title,
+        slug: slug,
+        file: file,
+        panels: None,
+        publish_at: publish_at,
+        date: date,
+        aliases: None,
+        series: series,
+        chapter: chapter,
+        tags: tags,
+        transcript: optional_text(parts, "transcript"),
+        hover_text: optional_text(parts, "hover_text"),
+        commentary: optional_text(parts, "commentary"),
+    }, path))
+}
+
+pub fn delete_form(app: &App, req: &mut Request) -> IronResult<Response> {
+    let csrf = match session_csrf(app, req) {
+        Some(csrf) => csrf,
+        None => return Ok(not_found()),
+    };
+
+    let slug = match route_param(req, "slug") {
+        Some(slug) => slug,
+        None => return Ok(not_found()),
+    };
+
+    with_index(app, |index| match index.find(&slug, true) {
+        Some(found) => Ok(view("views/admin/delete.html", Globals::new()
+            .with("url", found.url())
+            .with("title", template::escape(&found.title))
+            .with("slug", found.slug)
+            .with("csrf", csrf))),
+        None => Ok(not_found()),
+    })
+}
+
+pub fn delete(app: &App, req: &mut Request) -> IronResult<Response> {
+    let csrf = form_param(req, "csrf");
+    let user = match session_user_with_csrf(app, req, csrf) {
+        Some(user) => user,
+        None => return Ok(not_found()),
+    };
+
+    let slug = match route_param(req, "slug") {
+        Some(slug) => slug,
+        None => return Ok(not_found()),
+    };
+
+    with_index_and_resizer(app, |index, resizer| match index.remove(&slug, &user) {
+        Ok(Some(files)) => {
+            for file in &files {
+                resizer.purge(file);
+            }
+            Ok(redirect("/".into(), status::SeeOther))
+        },
+        Ok(None) => Ok(not_found()),
+        Err(e) => {
+            println!("Error deleting page: {}", e);
+            Ok(Response::with(status::InternalServerError))
+        },
+    })
+}
+
+pub fn revisions(app: &App, req: &mut Request) -> IronResult<Response> {
+    let csrf = match session_csrf(app, req) {
+        Some(csrf) => csrf,
+        None => return Ok(not_found()),
+    };
+
+    let mut vals = Globals::new();
+    vals.amend("message", match query_param(req, "restored") {
+        Some(count) => format!("<p>Restored {} page(s).</p>", template::escape(&count)),
+        None => "".into(),
+    });
+
+    with_index(app, |index| match index.revisions() {
+        Ok(revisions) => {
+            vals.amend("rows", revisions::render(&revisions, &csrf));
+            Ok(view("views/admin/revisions.html", vals))
+        },
+        Err(e) => {
+            println!("Error reading revisions: {}", e);
+            Ok(Response::with(status::InternalServerError))
+        },
+    })
+}
+
+pub fn restore(app: &App, req: &mut Request) -> IronResult<Response> {
+    let csrf = form_param(req, "csrf");
+    let user = match session_user_with_csrf(app, req, csrf) {
+        Some(user) => user,
+        None => return Ok(not_found()),
+    };
+
+    let id = route_param(req, "id").and_then(|id| id.parse::<usize>().ok());
+    let scope = route_param(req, "scope");
+    let (id, scope) = match (id, scope) {
+        (Some(id), Some(scope)) => (id, scope),
+        _ => return Ok(not_found()),
+    };
+
+    with_index(app, |index| {
+        let restored = match &scope[..] {
+            "page" => index.restore_page(id, &user).map(|changed| if changed { 1 } else { 0 }),
+            "index" => index.restore_index(id, &user),
+            _ => return Ok(not_found()),
+        };
+
+        match restored {
+            Ok(count) => Ok(redirect(format!("/admin/revisions?restored={}", count), status::SeeOther)),
+            Err(e) => {
+                println!("Error restoring revision {}: {}", id, e);
+                Ok(Response::with(status::InternalServerError))
+            },
+        }
+    })
+}
+
+pub fn login(_app: &App, req: &mut Request) -> IronResult<Response> {
+    println!("request method {:?}", req.method);
+    match req.method {
+        method::Method::Get => {
+            let mut vals = Globals::new();
+
+            vals.amend("message", match req.url.query {
+                Some(_) => "<span class=\"error\">Enter valid username and password.</span>".into(),
+                None => "".into(),
+            });
+
+            Ok(view("views/login.html", vals))
+        },
+        _ => {
+            Ok(not_found())
+        }
+    }
+}
+
+pub fn login_post(app: &App, req: &mut Request) -> IronResult<Response> {
+    println!("request method {:?}", req.method);
+    let user = match req.url.query {
+        Some(ref query) => {
+            let items = url::form_urlencoded::parse(query.as_bytes())
+                .into_iter()
+                .collect::<HashMap<_, _>>();
+
+            match (items.get("username").map(|v| v.as_ref()), items.get("password").map(|v| v.as_ref())) {
+                (Some(username), Some(pass)) => match app.users.lock() {
+                    Ok(ref u) if u.authorize(username, pass) => Some(username.to_string()),
+                    _ => None,
+                },
+                _ => None,
+            }
+        },
+        None => None,
+    };
+
+    if let Some(user) = user {
+        let token = random_str(120);
+        let expires = time::now_utc() + session_duration();
+        match app.sessions.lock() {
+            Ok(mut sessions) => sessions.insert(token.clone(), Session {
+                user: user,
+                csrf: random_str(32),
+                expires: expires,
+            }),
+            Err(e) => {
+                println!("Error locking sessions: {:?}", e);
+                return Ok(not_found());
+            }
+        };
+
+        let mut response = redirect("/".into(), status::SeeOther);
+        let mut cookie_pair = CookiePair::new("session".to_owned(), token);
+        cookie_pair.expires = Some(expires);
+        response.headers.set(
+           SetCookie(vec![
+               cookie_pair
+           ])
+        );
+
+        return Ok(response);
+    }
+
+    Ok(redirect("/login/?invalid".into(), status::SeeOther))
+}
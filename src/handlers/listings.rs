@@ -0,0 +1,149 @@
+use iron::prelude::*;
+use iron::status;
+use hyper::header::ContentType;
+use hyper::mime::{ Mime, TopLevel, SubLevel, Attr, Value };
+use url;
+
+use archive;
+use calendar;
+use feed;
+use sitemap;
+use api;
+use globals::Globals;
+use template;
+use handlers::{ App, with_index, with_index_and_resizer, not_found, redirect, view, json, xml, base_url,
+    route_param, query_param, check_admin };
+
+pub fn archive(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    with_index_and_resizer(app, |index, resizer| {
+        Ok(view("views/archive.html", Globals::new()
+            .with("archive", archive::render(index, resizer, admin_access))))
+    })
+}
+
+pub fn tag(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    let tag = match route_param(req, "name") {
+        Some(name) => url::percent_encoding::lossy_utf8_percent_decode(name.as_bytes()),
+        None => return Ok(not_found()),
+    };
+
+    with_index_and_resizer(app, |index, resizer| {
+        let entries = index.tagged_entries(&tag, admin_access);
+        if entries.len() == 0 {
+            return Ok(not_found());
+        }
+
+        let random_href = ["/random?tag=", url::percent_encoding::utf8_percent_encode(
+            &tag,
+            url::percent_encoding::FORM_URLENCODED_ENCODE_SET
+        ).as_ref()].concat();
+
+        Ok(view("views/tag.html", Globals::new()
+            .with("tag", template::escape(&tag))
+            .with("random_href", random_href)
+            .with("list", archive::render_list(&entries, resizer))))
+    })
+}
+
+pub fn search(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    let query = query_param(req, "q").unwrap_or(String::new());
+
+    with_index_and_resizer(app, |index, resizer| {
+        let results = if query.trim().len() > 0 {
+            let entries = index.search(&query, admin_access);
+            if entries.len() > 0 {
+                archive::render_list(&entries, resizer)
+            } else {
+                "<p>Nothing found.</p>".into()
+            }
+        } else {
+            String::new()
+        };
+
+        Ok(view("views/search.html", Globals::new()
+            .with("query", template::escape(&query))
+            .with("results", results)))
+    })
+}
+
+/// Redirects `/calendar` to the month of the latest page.
+pub fn latest_month(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    with_index(app, |index| match calendar::latest_month(index, admin_access) {
+        Some((year, month)) => Ok(redirect(calendar::calendar_url(year, month), status::SeeOther)),
+        None => Ok(not_found()),
+    })
+}
+
+pub fn calendar(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    let year = route_param(req, "year").and_then(|v| v.parse::<i32>().ok());
+    let month = route_param(req, "month").and_then(|v| v.parse::<u32>().ok());
+    let (year, month) = match (year, month) {
+        (Some(year), Some(month)) => (year, month),
+        _ => return Ok(not_found()),
+    };
+
+    with_index(app, |index| match calendar::render(index, year, month, admin_access) {
+        Some((title, calendar)) => Ok(view("views/calendar.html", Globals::new()
+            .with("title", title)
+            .with("calendar", calendar))),
+        None => Ok(not_found()),
+    })
+}
+
+pub fn rss(app: &App, req: &mut Request) -> IronResult<Response> {
+    with_index_and_resizer(app, |index, resizer| {
+        Ok(xml(feed::rss(index, resizer, &base_url(req)), "rss+xml"))
+    })
+}
+
+pub fn atom(app: &App, req: &mut Request) -> IronResult<Response> {
+    with_index_and_resizer(app, |index, resizer| {
+        Ok(xml(feed::atom(index, resizer, &base_url(req)), "atom+xml"))
+    })
+}
+
+pub fn sitemap(app: &App, req: &mut Request) -> IronResult<Response> {
+    with_index(app, |index| Ok(xml(sitemap::sitemap(index, &base_url(req)), "xml")))
+}
+
+pub fn robots(_app: &App, req: &mut Request) -> IronResult<Response> {
+    let mut response = Response::with((status::Ok, sitemap::robots(&base_url(req))));
+    response.headers.set(
+        ContentType(
+            Mime(TopLevel::Text, SubLevel::Plain, vec![(Attr::Charset, Value::Utf8)])
+        )
+    );
+    Ok(response)
+}
+
+pub fn api_list(app: &App, req: &mut Request) -> IronResult<Response> {
+    let page = match query_param(req, "page") {
+        Some(page) => match page.parse::<usize>() {
+            Ok(page) => page,
+            Err(_) => return Ok(not_found()),
+        },
+        None => 1,
+    };
+
+    with_index_and_resizer(app, |index, resizer| match api::list(index, resizer, page) {
+        Some(list) => Ok(json(&list)),
+        None => Ok(not_found()),
+    })
+}
+
+pub fn api_comic(app: &App, req: &mut Request) -> IronResult<Response> {
+    let slug = match route_param(req, "slug") {
+        Some(slug) => slug,
+        None => return Ok(not_found()),
+    };
+
+    with_index_and_resizer(app, |index, resizer| match api::comic(index, resizer, &slug) {
+        Some(comic) => Ok(json(&comic)),
+        None => Ok(not_found()),
+    })
+}
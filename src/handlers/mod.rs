@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{ Arc, Mutex };
+use iron::prelude::*;
+use iron::{ Handler, status };
+use hyper::header::{ CacheControl, CacheDirective, Location, ContentType, Cookie };
+use hyper::mime::{ Mime, TopLevel, SubLevel, Attr, Value };
+use router::Router;
+use serde;
+use serde_json;
+use time;
+use url;
+
+use index::Index;
+use resizer::Resizer;
+use commentary::Commentary;
+use users::Users;
+use globals;
+use template;
+
+pub mod pages;
+pub mod listings;
+pub mod admin;
+
+/// State shared by all request handlers.
+pub struct App {
+    pub index: Arc<Mutex<Index>>,
+    pub resizer: Mutex<Resizer>,
+    pub commentary: Mutex<Commentary>,
+    pub users: Mutex<Users>,
+    pub sessions: Mutex<HashMap<String, Session>>,
+}
+
+/// Logged in admin, keyed by the random token kept in the session cookie.
+/// Admin forms carry `csrf` token, so other sites can not post them with
+/// the cookie.
+pub struct Session {
+    pub user: String,
+    pub csrf: String,
+    pub expires: time::Tm,
+}
+
+/// How long a login lasts.
+pub fn session_duration() -> time::Duration {
+    time::Duration::hours(1)
+}
+
+/// Iron handler calling a plain function with the shared state.
+pub struct Route {
+    app: Arc<App>,
+    handler: fn(&App, &mut Request) -> IronResult<Response>,
+}
+
+impl Handler for Route {
+    fn handle(&self, req: &mut Request) -> IronResult<Response> {
+        (self.handler)(&self.app, req)
+    }
+}
+
+pub fn route(app: &Arc<App>, handler: fn(&App, &mut Request) -> IronResult<Response>) -> Route {
+    Route {
+        app: app.clone(),
+        handler: handler,
+    }
+}
+
+/// Runs `f` with locked index. Failure to lock is logged and answered with
+/// not found page.
+pub fn with_index<F>(app: &App, f: F) -> IronResult<Response>
+    where F: FnOnce(&mut Index) -> IronResult<Response>
+{
+    match app.index.lock() {
+        Ok(mut index) => f(&mut index),
+        Err(e) => {
+            println!("Error locking index: {:?}", e);
+            Ok(not_found())
+        }
+    }
+}
+
+/// Same as `with_index`, for handlers that also resize images.
+pub fn with_index_and_resizer<F>(app: &App, f: F) -> IronResult<Response>
+    where F: FnOnce(&mut Index, &mut Resizer) -> IronResult<Response>
+{
+    with_index(app, |index| match app.resizer.lock() {
+        Ok(mut resizer) => f(index, &mut resizer),
+        Err(e) => {
+            println!("Error locking resizer: {:?}", e);
+            Ok(not_found())
+        }
+    })
+}
+
+pub fn not_found() -> Response {
+    Response::with(status::NotFound)
+}
+
+pub fn redirect(url: String, status: status::Status) -> Response {
+    let mut response = Response::with(status);
+    response.headers.set(
+        Location(url)
+    );
+    response.headers.set(
+        CacheControl(vec![
+            CacheDirective::NoCache,
+        ])
+    );
+    response
+}
+
+pub fn view(path: &str, vals: globals::Globals) -> Response {
+    let parsed = template::parse(
+        path,
+        &vals
+    );
+    let mut response = Response::with((status::Ok, parsed));
+    response.headers.set(
+        ContentType(
+            Mime(TopLevel::Text, SubLevel::Html, vec![(Attr::Charset, Value::Utf8)])
+        )
+    );
+    response
+}
+
+pub use template::partial;
+
+pub fn json<T: serde::Serialize>(value: &T) -> Response {
+    match serde_json::to_string(value) {
+        Ok(serialized) => {
+            let mut response = Response::with((status::Ok, serialized));
+            response.headers.set(
+                ContentType(
+                    Mime(TopLevel::Application, SubLevel::Json, vec![(Attr::Charset, Value::Utf8)])
+                )
+            );
+            response
+        },
+        Err(e) => {
+            println!("Error serializing response: {:?}", e);
+            Response::with(status::InternalServerError)
+        }
+    }
+}
+
+pub fn xml(contents: String, kind: &str) -> Response {
+    let mut response = Response::with((status::Ok, contents));
+    response.headers.set(
+        ContentType(
+            Mime(TopLevel::Application, SubLevel::Ext(kind.into()), vec![(Attr::Charset, Value::Utf8)])
+        )
+    );
+    response
+}
+
+/// Scheme, host and port the request was made to, for absolute links.
+pub fn base_url(req: &Request) -> String {
+    match (req.url.scheme.as_ref(), req.url.port) {
+        ("http", 80) | ("https", 443) => format!("{}://{}", req.url.scheme, req.url.host),
+        _ => format!("{}://{}:{}", req.url.scheme, req.url.host, req.url.port),
+    }
+}
+
+pub fn append_link(
+    vals: &mut globals::Globals,
+    disabled_key: &'static str,
+    href_key: &'static str,
+    href: Option<String>
+) {
+    vals.amend(disabled_key, match href {
+        None => "disabled".into(),
+        _ => "".into(),
+    });
+    vals.amend(href_key, match href {
+        Some(href) => href,
+        None => "javascript:;".into(),
+    });
+}
+
+pub fn tag_url(tag: &str) -> String {
+    ["/tag/", url::percent_encoding::utf8_percent_encode(
+        tag,
+        url::percent_encoding::FORM_URLENCODED_ENCODE_SET
+    ).as_ref()].concat()
+}
+
+pub fn route_param(req: &Request, key: &str) -> Option<String> {
+    req.extensions.get::<Router>().unwrap().find(key).map(|v| v.to_string())
+}
+
+pub fn query_param(req: &Request, key: &str) -> Option<String> {
+    match req.url.query {
+        Some(ref query) => url::form_urlencoded::parse(query.as_bytes())
+            .into_iter()
+            .find(|&(ref k, _)| k == key)
+            .map(|(_, v)| v),
+        None => None,
+    }
+}
+
+/// Largest urlencoded form body read by `form_param`.
+const MAX_FORM_SIZE: u64 = 64 * 1024;
+
+/// Reads `key` from urlencoded POST body. The body is consumed, so this can
+/// be called once per request.
+pub fn form_param(req: &mut Request, key: &str) -> Option<String> {
+    let mut body = String::new();
+    if let Err(e) = req.body.by_ref().take(MAX_FORM_SIZE).read_to_string(&mut body) {
+        println!("Error reading form: {:?}", e);
+        return None;
+    }
+
+    url::form_urlencoded::parse(body.as_bytes())
+        .into_iter()
+        .find(|&(ref k, _)| k == key)
+        .map(|(_, v)| v)
+}
+
+pub fn cookie_value(req: &Request, key: &str) -> Option<String> {
+    match req.headers.get::<Cookie>() {
+        Some(&Cookie(ref vals)) => vals.iter()
+            .find(|v| v.name == key)
+            .map(|v| v.value.clone()),
+        None => None,
+    }
+}
+
+/// Runs `f` with the session of the logged in admin. Session cookie holds a
+/// token issued on login, the session is looked up on the server.
+fn with_session<T, F>(app: &App, req: &Request, f: F) -> Option<T>
+    where F: FnOnce(&Session) -> T
+{
+    let token = match cookie_value(req, "session") {
+        Some(token) => token,
+        None => return None,
+    };
+
+    match app.sessions.lock() {
+        Ok(mut sessions) => {
+            let now = time::get_time();
+            let expired: Vec<String> = sessions.iter()
+                .filter(|&(_, session)| session.expires.to_timespec() <= now)
+                .map(|(token, _)| token.clone())
+                .collect();
+            for token in expired {
+                sessions.remove(&token);
+            }
+            sessions.get(&token).map(f)
+        },
+        Err(e) => {
+            println!("Error locking sessions: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Returns name of the logged in admin.
+pub fn session_user(app: &App, req: &Request) -> Option<String> {
+    with_session(app, req, |session| session.user.clone())
+}
+
+/// Token to put into admin forms as `csrf` field.
+pub fn session_csrf(app: &App, req: &Request) -> Option<String> {
+    with_session(app, req, |session| session.csrf.clone())
+}
+
+/// Returns name of the logged in admin if `csrf` posted with the form
+/// matches the session.
+pub fn session_user_with_csrf(app: &App, req: &Request, csrf: Option<String>) -> Option<String> {
+    with_session(app, req, |session| match csrf {
+        Some(ref csrf) if *csrf == session.csrf => Some(session.user.clone()),
+        _ => None,
+    }).and_then(|user| user)
+}
+
+pub fn check_admin(app: &App, req: &Request) -> bool {
+    session_user(app, req).is_some()
+}
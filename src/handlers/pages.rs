@@ -0,0 +1,308 @@
+use iron::prelude::*;
+use iron::status;
+use hyper::header::SetCookie;
+use cookie::Cookie as CookiePair;
+use time;
+
+use index::Index;
+use resizer;
+use resizer::Resizer;
+use commentary::Commentary;
+use shuffle;
+use shuffle::Shuffle;
+use globals::Globals;
+use template;
+use handlers::{ App, with_index, with_index_and_resizer, not_found, redirect, view, partial, append_link,
+    tag_url, route_param, query_param, cookie_value, check_admin };
+
+/// Comic page at `/c/:slug` or `/c/:series/:slug`.
+pub fn page(app: &App, req: &mut Request) -> IronResult<Response> {
+    with_index_and_resizer(app, |index, resizer| match app.commentary.lock() {
+        Ok(mut commentary) => send_page(app, index, resizer, &mut commentary, req),
+        Err(e) => {
+            println!("Error locking commentary: {:?}", e);
+            Ok(not_found())
+        }
+    })
+}
+
+fn send_page(
+    app: &App,
+    index: &mut Index,
+    resizer: &mut Resizer,
+    commentary: &mut Commentary,
+    req: &mut Request
+) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    let series = route_param(req, "series");
+    let slug = match route_param(req, "slug") {
+        Some(slug) => slug,
+        None => return Ok(not_found()),
+    };
+
+    let found = match index.find(&slug, admin_access) {
+        Some(found) => found,
+        None => return match index.canonical_slug(&slug, admin_access) {
+            Some(canonical) => Ok(redirect(
+                index.permalink(&canonical),
+                status::MovedPermanently
+            )),
+            None => Ok(not_found()),
+        },
+    };
+
+    if found.series != series {
+        return Ok(redirect(found.url(), status::MovedPermanently));
+    }
+
+    let alt = template::escape(match found.transcript {
+        Some(ref transcript) => transcript,
+        None => &found.title,
+    });
+    let hover_text = match found.hover_text {
+        Some(ref hover_text) => template::escape(hover_text),
+        None => String::new(),
+    };
+
+    let images = found.files.iter()
+        .enumerate()
+        .map(|(i, file)| match resizer.get_resized_url(file, resizer::page_mode()) {
+            Some(r) => partial("views/page_image.html", &Globals::new()
+                .with("src", template::escape(&r.relative_url))
+                .with("width", r.size.w.to_string())
+                .with("height", r.size.h.to_string())
+                .with("alt", if i == 0 { alt.clone() } else { String::new() })
+                .with("title", hover_text.clone())),
+            None => String::new(),
+        })
+        .collect::<Vec<_>>()
+        .concat();
+
+    // newest page offers to continue from the bookmark, which is kept
+    // until the visitor reads on from it
+    let resume_title = match cookie_value(req, "bookmark") {
+        Some(ref bookmark) if *bookmark != found.slug
+            && Some(bookmark) != found.prev_slug.as_ref()
+            && index.last_slug(admin_access).as_ref() == Some(&found.slug) =>
+        {
+            index.find(bookmark, admin_access).map(|b| b.title)
+        },
+        _ => None,
+    };
+    let resuming = resume_title.is_some();
+
+    let mut vals = Globals::new()
+        .with("images", images)
+        .with("resume", match resume_title {
+            Some(title) => partial("views/resume.html", &Globals::new()
+                .with("title", template::escape(&title))),
+            None => String::new(),
+        })
+        .with("transcript", match found.transcript {
+            Some(ref transcript) => partial("views/transcript.html", &Globals::new()
+                .with("transcript", template::escape(transcript).replace("\n", "<br />"))),
+            None => String::new(),
+        })
+        .with("commentary", match commentary.get(
+            &found.file,
+            found.commentary.as_ref().map(|c| c.as_ref())
+        ) {
+            Some(html) => partial("views/commentary.html", &Globals::new()
+                .with("commentary", html)),
+            None => String::new(),
+        })
+        .with("title", template::escape(&found.title))
+        .with("slug", found.slug.clone());
+
+    if admin_access {
+        let controls = partial("views/admin/controls.html", &vals);
+        vals.amend("admin_controls", controls);
+    } else {
+        vals.amend("admin_controls", String::new());
+    }
+
+    vals.amend("tags", if found.tags.len() > 0 {
+        let links = found.tags.iter()
+            .map(|tag| partial("views/tag_link.html", &Globals::new()
+                .with("url", template::escape(&tag_url(tag)))
+                .with("tag", template::escape(tag))))
+            .collect::<Vec<_>>();
+        partial("views/tags.html", &Globals::new()
+            .with("links", links.join(", ")))
+    } else {
+        String::new()
+    });
+
+    match found.chapter {
+        Some(ref chapter) => {
+            let mut chapter_vals = Globals::new()
+                .with("chapter", chapter.clone());
+            append_link(&mut chapter_vals, "prev_chapter_disabled", "prev_chapter_href",
+                found.prev_chapter_slug.as_ref().map(|slug| index.permalink(slug)));
+            append_link(&mut chapter_vals, "next_chapter_disabled", "next_chapter_href",
+                found.next_chapter_slug.as_ref().map(|slug| index.permalink(slug)));
+            vals.amend("chapter_nav", partial("views/chapter_nav.html", &chapter_vals));
+        },
+        None => vals.amend("chapter_nav", String::new()),
+    }
+
+    let jump_vals = Globals::new()
+        .with("number", found.number.to_string())
+        .with("count", found.count.to_string())
+        .with("series", match found.series {
+            Some(ref series) => template::escape(series),
+            None => String::new(),
+        });
+    vals.amend("page_jump", partial("views/page_jump.html", &jump_vals));
+
+    append_link(&mut vals, "first_disabled", "first_href", match found.first_slug {
+        Some(slug) => if slug == found.slug {
+            None
+        } else {
+            Some(index.permalink(&slug))
+        },
+        None => None,
+    });
+
+    append_link(&mut vals, "prev_disabled", "prev_href", match found.prev_slug {
+        Some(slug) => Some(index.permalink(&slug)),
+        None => None,
+    });
+
+    append_link(&mut vals, "random_disabled", "random_href", Some(
+        ["/random?from=", found.slug.as_ref()].concat()
+    ));
+
+    append_link(&mut vals, "next_disabled", "next_href", match found.next_slug {
+        Some(slug) => Some(index.permalink(&slug)),
+        None => None,
+    });
+
+    append_link(&mut vals, "last_disabled", "last_href", match found.last_slug {
+        Some(slug) => if slug == found.slug {
+            None
+        } else {
+            Some(index.permalink(&slug))
+        },
+        None => None,
+    });
+
+    let mut response = view("views/comic.html", vals);
+    if !resuming {
+        response.headers.set(SetCookie(vec![bookmark_cookie(&found.slug)]));
+    }
+    Ok(response)
+}
+
+/// Remembers last page read by the visitor for a year.
+fn bookmark_cookie(slug: &str) -> CookiePair {
+    let mut cookie_pair = CookiePair::new("bookmark".to_owned(), slug.to_owned());
+    cookie_pair.path = Some("/".into());
+    cookie_pair.expires = Some(time::now_utc() + time::Duration::days(365));
+    cookie_pair
+}
+
+/// Redirects to the latest visible page.
+pub fn latest(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    with_index(app, |index| match index.last_slug(admin_access) {
+        Some(slug) => Ok(redirect(index.permalink(&slug), status::SeeOther)),
+        None => {
+            println!("No pages exist");
+            Ok(not_found())
+        }
+    })
+}
+
+/// Redirects `/:year/:month/:day` to the page published that day.
+pub fn by_date(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    let req: &Request = req;
+    let number = |key: &str| route_param(req, key).and_then(|v| v.parse::<u32>().ok());
+    let date = match (number("year"), number("month"), number("day")) {
+        (Some(year), Some(month), Some(day)) => format!("{:04}-{:02}-{:02}", year, month, day),
+        _ => return Ok(not_found()),
+    };
+
+    with_index(app, |index| match index.find_by_date(&date, admin_access) {
+        Some(slug) => Ok(redirect(index.permalink(&slug), status::SeeOther)),
+        None => Ok(not_found()),
+    })
+}
+
+/// Redirects to the bookmarked page, or to the latest one without bookmark.
+pub fn resume(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    let bookmark = cookie_value(req, "bookmark");
+    with_index(app, |index| {
+        let bookmarked = bookmark.and_then(|slug| index.find(&slug, admin_access).map(|found| found.slug));
+        match bookmarked.or(index.last_slug(admin_access)) {
+            Some(slug) => Ok(redirect(index.permalink(&slug), status::SeeOther)),
+            None => {
+                println!("No pages exist");
+                Ok(not_found())
+            }
+        }
+    })
+}
+
+/// Redirects `/p/:number` and `/p/:series/:number` to the page permalink.
+pub fn numbered(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    let series = route_param(req, "series");
+    let number = match route_param(req, "number").and_then(|n| n.parse::<usize>().ok()) {
+        Some(number) => number,
+        None => return Ok(not_found()),
+    };
+
+    with_index(app, |index| match index.slug_by_number(series.as_ref().map(|s| &s[..]), number, admin_access) {
+        Some(slug) => Ok(redirect(index.permalink(&slug), status::SeeOther)),
+        None => Ok(not_found()),
+    })
+}
+
+/// Handles jump-to-page form. Numbers that are not valid or out of range
+/// get the not found page.
+pub fn jump(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    let series = query_param(req, "series").and_then(|s| if s.len() > 0 { Some(s) } else { None });
+    let number = match query_param(req, "number").and_then(|n| n.trim().parse::<usize>().ok()) {
+        Some(number) => number,
+        None => return Ok(not_found()),
+    };
+    with_index(app, |index| match index.slug_by_number(series.as_ref().map(|s| &s[..]), number, admin_access) {
+        Some(slug) => Ok(redirect(index.permalink(&slug), status::SeeOther)),
+        None => Ok(not_found()),
+    })
+}
+
+/// Redirects to the next page in visitor's shuffled order. Each tag keeps
+/// its own order.
+pub fn random(app: &App, req: &mut Request) -> IronResult<Response> {
+    let admin_access = check_admin(app, req);
+    let tag = query_param(req, "tag");
+    let from = query_param(req, "from");
+    let cookie_name = shuffle::cookie_name(tag.as_ref().map(|t| t.as_ref()));
+    let mut shuffle = cookie_value(req, &cookie_name)
+        .and_then(|value| Shuffle::parse(&value))
+        .unwrap_or(Shuffle::new());
+
+    with_index(app, |index| {
+        let tag = tag.as_ref().map(|t| t.as_ref());
+        let from = from.as_ref().map(|f| f.as_ref());
+        match index.random_slug(admin_access, tag, from, &mut shuffle) {
+            Some(slug) => {
+                let mut response = redirect(index.permalink(&slug), status::SeeOther);
+                let mut cookie_pair = CookiePair::new(cookie_name, shuffle.to_cookie());
+                cookie_pair.path = Some("/".into());
+                cookie_pair.expires = Some(time::now_utc() + time::Duration::days(30));
+                response.headers.set(SetCookie(vec![cookie_pair]));
+                Ok(response)
+            },
+            None => {
+                println!("No pages exist");
+                Ok(not_found())
+            },
+        }
+    })
+}
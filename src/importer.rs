@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::{ Path, PathBuf };
+
+use index;
+use index::Index;
+use index_models::IndexRepr;
+
+struct Candidate {
+    path: PathBuf,
+    file: String,
+    title: String,
+    slug: String,
+    date: Option<String>,
+}
+
+fn is_image(path: &Path) -> bool {
+    match path.extension().map(|ext| ext.to_string_lossy().to_lowercase()) {
+        Some(ref ext) => ext == "png" || ext == "jpg" || ext == "jpeg" || ext == "gif",
+        None => false,
+    }
+}
+
+/// Splits `2016-05-12-some-title` into the date and the rest.
+fn split_date(stem: &str) -> (Option<String>, &str) {
+    if stem.len() >= 10 && stem.is_char_boundary(10) {
+        let (date, rest) = stem.split_at(10);
+        if let Some(_) = index::parse_date(date) {
+            return (Some(date.to_string()), rest.trim_left_matches(|c: char| c == '-' || c == '_' || c == ' '));
+        }
+    }
+    (None, stem)
+}
+
+fn to_slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars().flat_map(|c| c.to_lowercase()) {
+        match c {
+            'a'...'z' | '0'...'9' => slug.push(c),
+            _ => if slug.len() > 0 && !slug.ends_with('-') {
+                slug.push('-');
+            },
+        }
+    }
+    slug.trim_right_matches('-').to_string()
+}
+
+fn to_title(name: &str) -> String {
+    let words: Vec<&str> = name.split(|c: char| c == '-' || c == '_' || c == ' ')
+        .filter(|word| word.len() > 0)
+        .collect();
+    let title = words.join(" ");
+
+    let mut chars = title.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => title,
+    }
+}
+
+/// Derives page from file name convention `[YYYY-MM-DD-]title-words.ext`.
+fn candidate(path: &Path) -> Option<Candidate> {
+    let file = match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => return None,
+    };
+    let stem = match path.file_stem() {
+        Some(stem) => stem.to_string_lossy().into_owned(),
+        None => return None,
+    };
+
+    let (date, name) = split_date(&stem);
+    let name = if name.len() > 0 { name } else { &stem[..] };
+
+    Some(Candidate {
+        path: path.into(),
+        file: file.clone(),
+        title: to_title(name),
+        slug: to_slug(name),
+        date: date,
+    })
+}
+
+/// Returns `slug`, or `page` for an empty one, numbered while `is_taken`.
+fn unique_slug<F>(slug: &str, is_taken: F) -> String
+    where F: Fn(&str) -> bool
+{
+    let base = if slug.len() > 0 { slug.to_string() } else { "page".to_string() };
+    let mut result = base.clone();
+    let mut n = 2;
+    while is_taken(&result) {
+        result = format!("{}-{}", base, n);
+        n += 1;
+    }
+    result
+}
+
+/// Appends images from `dir` that no page uses yet, ordered by date and file
+/// name. Images of deleted pages are skipped. Images from directories other
+/// than `images_path` are copied there. In dry run only prints what would be
+/// added. Returns `false` on errors.
+pub fn run(index: &mut Index, images_path: &Path, dir: &Path, dry_run: bool) -> bool {
+    let list = match fs::read_dir(dir) {
+        Ok(list) => list,
+        Err(e) => {
+            println!("failed to read dir {:?}: {:?}", dir, e);
+            return false;
+        }
+    };
+
+    let deleted = match index.deleted_files() {
+        Ok(deleted) => deleted,
+        Err(e) => {
+            println!("{}", e);
+            return false;
+        }
+    };
+
+    let used = index.files();
+    let mut candidates: Vec<Candidate> = list
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_image(path))
+        .filter_map(|path| candidate(&path))
+        .filter(|c| !used.contains(&c.file))
+        .filter(|c| if deleted.contains(&c.file) {
+            println!("skipping {:?}, its page was deleted", c.file);
+            false
+        } else {
+            true
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| (&a.date, &a.file).cmp(&(&b.date, &b.file)));
+
+    let copy = match (fs::canonicalize(dir), fs::canonicalize(images_path)) {
+        (Ok(dir), Ok(images_path)) => dir != images_path,
+        _ => true,
+    };
+
+    let mut taken: Vec<String> = Vec::new();
+    let mut ok = true;
+
+    for c in candidates {
+        let slug = unique_slug(&c.slug, |slug| index.contains(slug) || taken.iter().any(|t| t == slug));
+        taken.push(slug.clone());
+
+        println!(
+            "{} {:?} as {:?} titled {:?}{}",
+            if dry_run { "would add" } else { "adding" },
+            c.file,
+            slug,
+            c.title,
+            match c.date {
+                Some(ref date) => format!(", dated {}", date),
+                None => String::new(),
+            }
+        );
+
+        if dry_run {
+            continue;
+        }
+
+        if copy {
+            let target = images_path.join(&c.file);
+            if let Ok(_) = fs::metadata(&target) {
+                println!("  {:?} already exists, skipping", target);
+                ok = false;
+                continue;
+            }
+            if let Err(e) = fs::copy(&c.path, &target) {
+                println!("  failed to copy to {:?}: {:?}", target, e);
+                ok = false;
+                continue;
+            }
+        }
+
+        if let Err(e) = index.push(IndexRepr {
+            title: c.title,
+            slug: slug,
+            file: c.file,
+            panels: None,
+            publish_at: None,
+            date: c.date,
+            aliases: None,
+            series: None,
+            chapter: None,
+            tags: None,
+            transcript: None,
+            hover_text: None,
+            commentary: None,
+        }, "import") {
+            println!("  {}", e);
+            ok = false;
+        }
+    }
+
+    ok
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ split_date, to_slug, to_title, unique_slug };
+
+    #[test]
+    fn splits_date_from_name() {
+        assert_eq!(split_date("2016-05-12-some-title"), (Some("2016-05-12".into()), "some-title"));
+        assert_eq!(split_date("2016-05-12_some title"), (Some("2016-05-12".into()), "some title"));
+        assert_eq!(split_date("2016-05-12"), (Some("2016-05-12".into()), ""));
+    }
+
+    #[test]
+    fn keeps_undated_name() {
+        assert_eq!(split_date("some-title"), (None, "some-title"));
+        assert_eq!(split_date("2016-13-45-title"), (None, "2016-13-45-title"));
+        assert_eq!(split_date("2016"), (None, "2016"));
+        assert_eq!(split_date("котик-и-пёс"), (None, "котик-и-пёс"));
+        assert_eq!(split_date("日本語のタイトル"), (None, "日本語のタイトル"));
+    }
+
+    #[test]
+    fn makes_slug() {
+        assert_eq!(to_slug("Some Title"), "some-title");
+        assert_eq!(to_slug("some__title--2"), "some-title-2");
+        assert_eq!(to_slug("  Title!  "), "title");
+        assert_eq!(to_slug("Café au lait"), "caf-au-lait");
+        assert_eq!(to_slug("котик"), "");
+        assert_eq!(to_slug("日本語のタイトル"), "");
+    }
+
+    #[test]
+    fn makes_title() {
+        assert_eq!(to_title("some-title_words here"), "Some title words here");
+        assert_eq!(to_title("--title--"), "Title");
+        assert_eq!(to_title("ёжик"), "Ёжик");
+        assert_eq!(to_title(""), "");
+    }
+
+    #[test]
+    fn numbers_taken_slugs() {
+        let taken = ["cat", "cat-2", "page"];
+        let is_taken = |slug: &str| taken.contains(&slug);
+
+        assert_eq!(unique_slug("dog", &is_taken), "dog");
+        assert_eq!(unique_slug("cat", &is_taken), "cat-3");
+        assert_eq!(unique_slug("", &is_taken), "page-2");
+        assert_eq!(unique_slug(&to_slug("котик"), |_| false), "page");
+    }
+}
@@ -1,32 +1,50 @@
-use std::collections::HashMap;
-use std::path::{ Path, PathBuf };
-use serde_json;
-use std::io::prelude::*;
-use std::fs::File;
-use rand;
-use rand::distributions::{IndependentSample, Range};
+use std::collections::{ HashMap, HashSet };
+use std::mem;
+use std::path::Path;
+use time;
+use time::Timespec;
 
 use index_models::IndexRepr;
+use search::SearchIndex;
+use shuffle::Shuffle;
+use backend;
+use backend::Backend;
+use errors::LoadError;
+use revisions;
+use revisions::Revisions;
+use revisions_models::RevisionRepr;
 
 #[derive(Debug)]
 pub struct IndexItem {
     title: String,
     slug: String,
     file: String,
+    panels: Vec<String>,
+    publish_at: Option<Timespec>,
+    date: Option<Timespec>,
+    aliases: Vec<String>,
+    series: Option<String>,
+    chapter: Option<String>,
+    tags: Vec<String>,
+    transcript: Option<String>,
+    hover_text: Option<String>,
+    commentary: Option<String>,
     prev: Option<usize>,
     next: Option<usize>,
 }
 
-#[derive(Debug)]
 pub struct Index {
-    path: PathBuf,
+    backend: Box<Backend>,
     storage: Storage,
+    revisions: Revisions,
 }
 
 #[derive(Debug)]
 struct Storage {
     items: Vec<IndexItem>,
     slug_map: HashMap<String, usize>,
+    alias_map: HashMap<String, usize>,
+    search: SearchIndex,
 }
 
 #[derive(Debug)]
@@ -34,102 +52,558 @@ struct FoundIndex {
     pub title: String,
     pub slug: String,
     pub file: String,
+    pub files: Vec<String>,
+    pub publish_at: Option<Timespec>,
+    pub series: Option<String>,
+    pub chapter: Option<String>,
+    pub tags: Vec<String>,
+    pub transcript: Option<String>,
+    pub hover_text: Option<String>,
+    pub commentary: Option<String>,
     pub prev_slug: Option<String>,
     pub next_slug: Option<String>,
+    pub first_slug: Option<String>,
+    pub last_slug: Option<String>,
+    pub prev_chapter_slug: Option<String>,
+    pub next_chapter_slug: Option<String>,
+    pub number: usize,
+    pub count: usize,
 }
 
-impl Storage {
-    fn from_file(path: &Path) -> Option<Storage> {
-        let mut f = match File::open(path) {
-            Ok(f) => f,
-            _ => {
-                println!("failed to open index storage");
-                return None;
+/// Public view of a single item, used for listings.
+#[derive(Debug)]
+pub struct Entry {
+    pub title: String,
+    pub slug: String,
+    pub file: String,
+    pub files: Vec<String>,
+    pub publish_at: Option<Timespec>,
+    pub date: Option<Timespec>,
+    pub series: Option<String>,
+    pub chapter: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Entry {
+    pub fn url(&self) -> String {
+        page_url(self.series.as_ref().map(|s| s.as_ref()), &self.slug)
+    }
+}
+
+impl FoundIndex {
+    pub fn url(&self) -> String {
+        page_url(self.series.as_ref().map(|s| s.as_ref()), &self.slug)
+    }
+}
+
+/// Checks if value can be used as a single URL path segment as is.
+pub fn is_valid_slug(slug: &str) -> bool {
+    slug.len() > 0 && slug.chars().all(|c| match c {
+        'a'...'z' | 'A'...'Z' | '0'...'9' | '-' | '_' => true,
+        _ => false,
+    })
+}
+
+/// Builds permalink of the page. Pages of the default series live directly
+/// under `/c/`, others under `/c/:series/`.
+pub fn page_url(series: Option<&str>, slug: &str) -> String {
+    match series {
+        Some(series) => ["/c/", series, "/", slug].concat(),
+        None => ["/c/", slug].concat(),
+    }
+}
+
+/// Parses `2016-05-12T10:00:00Z` or `2016-05-12` as UTC time.
+pub fn parse_time(value: &str) -> Option<Timespec> {
+    match time::strptime(value, "%Y-%m-%dT%H:%M:%SZ") {
+        Ok(tm) => Some(tm.to_timespec()),
+        Err(_) => match time::strptime(value, "%Y-%m-%d") {
+            Ok(tm) => Some(tm.to_timespec()),
+            Err(_) => None,
+        },
+    }
+}
+
+pub fn format_time(value: Timespec) -> String {
+    format!("{}", time::at_utc(value).rfc3339())
+}
+
+/// Parses `2016-05-12` as UTC midnight.
+pub fn parse_date(value: &str) -> Option<Timespec> {
+    match time::strptime(value, "%Y-%m-%d") {
+        Ok(tm) => Some(tm.to_timespec()),
+        Err(_) => None,
+    }
+}
+
+pub fn format_date(value: Timespec) -> String {
+    let tm = time::at_utc(value);
+    format!("{:04}-{:02}-{:02}", tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday)
+}
+
+/// Builds date permalink like `/2016/05/12`.
+pub fn date_url(value: Timespec) -> String {
+    ["/", format_date(value).replace("-", "/").as_ref()].concat()
+}
+
+impl IndexItem {
+    fn from_repr(repr: IndexRepr) -> Result<IndexItem, String> {
+        let publish_at = match repr.publish_at {
+            Some(ref value) => match parse_time(value) {
+                Some(time) => Some(time),
+                None => return Err(format!("invalid publish_at {:?} for {:?}", value, repr.slug)),
             },
+            None => None,
         };
+        let date = match repr.date {
+            Some(ref value) => match parse_date(value) {
+                Some(date) => Some(date),
+                None => return Err(format!("invalid date {:?} for {:?}", value, repr.slug)),
+            },
+            None => None,
+        };
+
+        Ok(IndexItem {
+            title: repr.title,
+            slug: repr.slug,
+            file: repr.file,
+            panels: repr.panels.unwrap_or(Vec::new()),
+            publish_at: publish_at,
+            date: date,
+            aliases: repr.aliases.unwrap_or(Vec::new()),
+            series: repr.series,
+            chapter: repr.chapter,
+            tags: repr.tags.unwrap_or(Vec::new()),
+            transcript: repr.transcript,
+            hover_text: repr.hover_text,
+            commentary: repr.commentary,
+            prev: None,
+            next: None,
+        })
+    }
+
+    fn to_repr(&self) -> IndexRepr {
+        IndexRepr {
+            title: self.title.clone(),
+            slug: self.slug.clone(),
+            file: self.file.clone(),
+            panels: if self.panels.len() > 0 {
+                Some(self.panels.clone())
+            } else {
+                None
+            },
+            publish_at: self.publish_at.map(format_time),
+            date: self.date.map(format_date),
+            aliases: if self.aliases.len() > 0 {
+                Some(self.aliases.clone())
+            } else {
+                None
+            },
+            series: self.series.clone(),
+            chapter: self.chapter.clone(),
+            tags: if self.tags.len() > 0 {
+                Some(self.tags.clone())
+            } else {
+                None
+            },
+            transcript: self.transcript.clone(),
+            hover_text: self.hover_text.clone(),
+            commentary: self.commentary.clone(),
+        }
+    }
 
-        let mut contents = String::new();
-        if let Err(e) = f.read_to_string(&mut contents) {
-            panic!("error reading index storage {:?}", e);
+    fn to_entry(&self) -> Entry {
+        Entry {
+            title: self.title.clone(),
+            slug: self.slug.clone(),
+            file: self.file.clone(),
+            files: self.files(),
+            publish_at: self.publish_at,
+            date: self.published_on(),
+            series: self.series.clone(),
+            chapter: self.chapter.clone(),
+            tags: self.tags.clone(),
         }
+    }
 
-        let deserialized: Vec<IndexRepr> = match serde_json::from_str(&contents) {
-            Ok(contents) => contents,
-            Err(e) => {
-                panic!("error deserializing index storage {:?}", e);
-            }
-        };
+    /// Day the page is known by: its publication date, or the day it was
+    /// scheduled for.
+    fn published_on(&self) -> Option<Timespec> {
+        self.date.or(self.publish_at)
+    }
+
+    /// All images of the page in order: the main file, then extra panels.
+    fn files(&self) -> Vec<String> {
+        let mut files = vec![self.file.clone()];
+        files.extend(self.panels.iter().cloned());
+        files
+    }
 
+    fn has_tag(&self, tag: Option<&str>) -> bool {
+        match tag {
+            Some(tag) => self.tags.iter().any(|t| t == tag),
+            None => true,
+        }
+    }
+
+    fn is_published(&self, now: Timespec) -> bool {
+        match self.publish_at {
+            Some(publish_at) => publish_at <= now,
+            None => true,
+        }
+    }
+}
+
+impl Storage {
+    fn from_reprs(reprs: Vec<IndexRepr>) -> Result<Storage, String> {
         let mut storage = Storage::empty();
 
-        for item in deserialized {
-            storage.push(item.title, item.slug, &item.file);
+        for repr in reprs {
+            if storage.slug_map.contains_key(&repr.slug) {
+                println!("duplicate slug {:?} in index, earlier page is unreachable", repr.slug);
+            }
+            storage.push(try!(IndexItem::from_repr(repr)));
         }
 
-        Some(storage)
+        Ok(storage)
     }
 
     fn empty() -> Storage {
         Storage {
             items: Vec::new(),
             slug_map: HashMap::new(),
+            alias_map: HashMap::new(),
+            search: SearchIndex::new(),
         }
     }
 
-    fn push<T: Into<String>, S: Into<String>>(&mut self, title: T, slug: S, file: &str) {
-        let title: String = title.into();
-        let slug: String = slug.into();
-        let prev_index = if self.items.len() == 0 {
-            None
-        } else {
-            Some(self.items.len() - 1)
-        };
+    /// Appends item and links it after the last item of the same series.
+    fn push(&mut self, mut item: IndexItem) {
+        let index = self.items.len();
+        let prev_index = self.items.iter().rposition(|other| other.series == item.series);
         if let Some(last_index) = prev_index {
             if let Some(last_item) = self.items.get_mut(last_index) {
-                last_item.next = Some(last_index + 1);
+                last_item.next = Some(index);
             }
         }
-        self.items.push(IndexItem {
-            title: title,
-            slug: slug.clone(),
-            file: file.into(),
-            prev: prev_index,
-            next: None,
-        });
-        self.slug_map.insert(slug, self.items.len() - 1);
+        item.prev = prev_index;
+        item.next = None;
+        self.slug_map.insert(item.slug.clone(), index);
+        for alias in &item.aliases {
+            self.alias_map.insert(alias.clone(), index);
+        }
+        self.search.add(index, &item.title);
+        if let Some(ref transcript) = item.transcript {
+            self.search.add(index, transcript);
+        }
+        self.items.push(item);
+    }
+
+    fn remove(&mut self, slug: &str) -> Option<IndexItem> {
+        let index = match self.slug_map.get(slug) {
+            Some(index) => *index,
+            None => return None,
+        };
+
+        let removed = self.items.remove(index);
+        self.relink();
+
+        Some(removed)
+    }
+
+    fn replace(&mut self, index: usize, item: IndexItem) {
+        self.items[index] = item;
+        self.relink();
+    }
+
+    /// Rebuilds prev/next offsets, slug and alias maps after items were moved.
+    fn relink(&mut self) {
+        let items = mem::replace(&mut self.items, Vec::new());
+        self.slug_map.clear();
+        self.alias_map.clear();
+        self.search.clear();
+
+        for item in items {
+            self.push(item);
+        }
     }
 }
 
 impl Index {
-    pub fn from_file(path: &str) -> Index {
-        let path = Path::new(path);
-        let storage = match Storage::from_file(path) {
-            Some(s) => s,
-            None => Storage::empty(),
-        };
+    pub fn open(path: &Path) -> Result<Index, LoadError> {
+        let backend = try!(backend::open(path));
+        let storage = try!(Storage::from_reprs(try!(backend.list()))
+            .map_err(|e| LoadError::Invalid { path: path.into(), message: e }));
 
-        Index {
-            path: path.into(),
+        Ok(Index {
+            backend: backend,
             storage: storage,
+            revisions: Revisions::new(&revisions::log_path(path)),
+        })
+    }
+
+    /// Loads index and logs the error if it fails, for reloads while serving.
+    pub fn load(path: &Path) -> Option<Index> {
+        match Index::open(path) {
+            Ok(index) => Some(index),
+            Err(e) => {
+                println!("{}", e);
+                None
+            },
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.backend.path()
+    }
+
+    fn invalid(&self, message: String) -> LoadError {
+        LoadError::Invalid { path: self.path().into(), message: message }
+    }
+
+    /// Returns names of all image files used by pages.
+    pub fn files(&self) -> HashSet<String> {
+        self.storage.items.iter()
+            .flat_map(|item| item.files().into_iter())
+            .collect()
+    }
+
+    /// Returns names of image files that belonged to deleted pages and are
+    /// not used by any page now. Delete keeps the files so the page can be
+    /// restored from revisions.
+    pub fn deleted_files(&self) -> Result<HashSet<String>, LoadError> {
+        let used = self.files();
+        let revisions = try!(self.revisions.list());
+        Ok(revisions.into_iter()
+            .filter(|revision| revision.after.is_none())
+            .filter_map(|revision| revision.before)
+            .flat_map(|before| {
+                let mut files = vec![before.file];
+                files.extend(before.panels.unwrap_or(Vec::new()));
+                files.into_iter()
+            })
+            .filter(|file| !used.contains(file))
+            .collect())
+    }
+
+    /// Checks if slug is taken, either as a current slug or an old alias.
+    pub fn contains(&self, slug: &str) -> bool {
+        self.storage.slug_map.contains_key(slug) || self.storage.alias_map.contains_key(slug)
+    }
+
+    pub fn push(&mut self, repr: IndexRepr, user: &str) -> Result<(), LoadError> {
+        if self.contains(&repr.slug) {
+            return Err(self.invalid(format!("slug {:?} already exists", repr.slug)));
+        }
+        if let Some(ref aliases) = repr.aliases {
+            if let Some(alias) = aliases.iter().find(|alias| self.contains(alias)) {
+                return Err(self.invalid(format!("alias {:?} already exists", alias)));
+            }
+        }
+        let item = try!(IndexItem::from_repr(repr.clone()).map_err(|e| self.invalid(e)));
+        let after = item.to_repr();
+        try!(self.backend.insert(&repr));
+        self.storage.push(item);
+        self.record(user, None, Some(after));
+        Ok(())
+    }
+
+    /// Replaces contents of existing item, keeping its place in the index.
+    pub fn update(&mut self, slug: &str, repr: IndexRepr, user: &str) -> Result<(), LoadError> {
+        let index = match self.storage.slug_map.get(slug) {
+            Some(index) => *index,
+            None => return Err(self.invalid(format!("slug {:?} does not exist", slug))),
+        };
+        if repr.slug != slug {
+            return Err(self.invalid(format!("slug {:?} can not be changed to {:?}", slug, repr.slug)));
+        }
+        if let Some(ref aliases) = repr.aliases {
+            let taken = aliases.iter().find(|alias| {
+                let owner = self.storage.slug_map.get(*alias).or(self.storage.alias_map.get(*alias));
+                owner.is_some() && owner != Some(&index)
+            });
+            if let Some(alias) = taken {
+                return Err(self.invalid(format!("alias {:?} already exists", alias)));
+            }
+        }
+        let item = try!(IndexItem::from_repr(repr.clone()).map_err(|e| self.invalid(e)));
+        let before = self.storage.items[index].to_repr();
+        let after = item.to_repr();
+        try!(self.backend.update(slug, &repr));
+        self.storage.replace(index, item);
+        self.record(user, Some(before), Some(after));
+        Ok(())
+    }
+
+    /// Removes item from storage backend and memory, returns names of its
+    /// image files.
+    pub fn remove(&mut self, slug: &str, user: &str) -> Result<Option<Vec<String>>, LoadError> {
+        let before = match self.storage.slug_map.get(slug) {
+            Some(index) => self.storage.items[*index].to_repr(),
+            None => return Ok(None),
+        };
+        try!(self.backend.delete(slug));
+        let files = self.storage.remove(slug).map(|item| item.files());
+        self.record(user, Some(before), None);
+        Ok(files)
+    }
+
+    /// Adds change to revision log. Failing to record it does not undo the
+    /// change, which is already saved.
+    fn record(&self, user: &str, before: Option<IndexRepr>, after: Option<IndexRepr>) {
+        if let Err(e) = self.revisions.append(user, before, after) {
+            println!("{}", e);
+        }
+    }
+
+    /// Returns revision log, oldest first.
+    pub fn revisions(&self) -> Result<Vec<RevisionRepr>, LoadError> {
+        self.revisions.list()
+    }
+
+    /// Brings the page changed by revision `id` back to the state it had
+    /// before that revision. Returns `false` if it is already in that state.
+    pub fn restore_page(&mut self, id: usize, user: &str) -> Result<bool, LoadError> {
+        let revisions = try!(self.revisions.list());
+        if id == 0 || id > revisions.len() {
+            return Err(self.invalid(format!("revision {} does not exist", id)));
         }
+        let revision = revisions.into_iter().nth(id - 1).unwrap();
+        self.restore(&revision.slug, revision.before, user)
     }
 
-    pub fn find<'r>(&self, slug: &'r str) -> Option<FoundIndex> {
+    /// Brings every page changed since revision `id` back to the state it had
+    /// before that revision. Returns the number of changed pages. Removed
+    /// pages come back at the end of the index.
+    pub fn restore_index(&mut self, id: usize, user: &str) -> Result<usize, LoadError> {
+        let revisions = try!(self.revisions.list());
+        if id == 0 || id > revisions.len() {
+            return Err(self.invalid(format!("revision {} does not exist", id)));
+        }
+
+        let mut targets: Vec<(String, Option<IndexRepr>)> = Vec::new();
+        for revision in revisions.into_iter().skip(id - 1) {
+            if !targets.iter().any(|&(ref slug, _)| *slug == revision.slug) {
+                targets.push((revision.slug, revision.before));
+            }
+        }
+
+        let mut changed = 0;
+        for (slug, target) in targets {
+            if try!(self.restore(&slug, target, user)) {
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Adds, changes or removes the page so it matches `target`, recording
+    /// it as a new revision.
+    fn restore(&mut self, slug: &str, target: Option<IndexRepr>, user: &str) -> Result<bool, LoadError> {
+        let current = self.storage.slug_map.get(slug).map(|index| self.storage.items[*index].to_repr());
+        match (current, target) {
+            (Some(ref current), Some(ref target)) if current == target => Ok(false),
+            (None, None) => Ok(false),
+            (Some(_), None) => self.remove(slug, user).map(|_| true),
+            (None, Some(target)) => self.push(target, user).map(|_| true),
+            (Some(_), Some(target)) => self.update(slug, target, user).map(|_| true),
+        }
+    }
+
+    /// Checks if item can be shown. Queued items are visible only in preview,
+    /// which is used for admins.
+    fn is_visible(&self, item: &IndexItem, preview: bool) -> bool {
+        preview || item.is_published(time::get_time())
+    }
+
+    fn find_visible(&self, index: Option<usize>, forward: bool, preview: bool) -> Option<&IndexItem> {
+        self.find_visible_until(index, None, forward, preview)
+    }
+
+    /// Walks series links from `index` and returns first visible item,
+    /// giving up after the `stop` item.
+    fn find_visible_until(&self, mut index: Option<usize>, stop: Option<usize>, forward: bool, preview: bool)
+        -> Option<&IndexItem>
+    {
+        while let Some(i) = index {
+            let item = &self.storage.items[i];
+            if self.is_visible(item, preview) {
+                return Some(item);
+            }
+            if Some(i) == stop {
+                break;
+            }
+            index = if forward { item.next } else { item.prev };
+        }
+        None
+    }
+
+    /// Returns offsets of the first and the last item in the chapter run of
+    /// the series. Series without chapters is a single run.
+    fn chapter_bounds(&self, index: usize) -> (usize, usize) {
+        let items = &self.storage.items;
+        let chapter = &items[index].chapter;
+
+        let mut start = index;
+        while let Some(prev) = items[start].prev {
+            if items[prev].chapter != *chapter {
+                break;
+            }
+            start = prev;
+        }
+
+        let mut end = index;
+        while let Some(next) = items[end].next {
+            if items[next].chapter != *chapter {
+                break;
+            }
+            end = next;
+        }
+
+        (start, end)
+    }
+
+    pub fn find<'r>(&self, slug: &'r str, preview: bool) -> Option<FoundIndex> {
         match self.storage.slug_map.get(slug) {
             Some(index) => {
                 let item = &self.storage.items[*index];
-                let next = match item.next {
-                    Some(index) => Some(&self.storage.items[index]),
+                if !self.is_visible(item, preview) {
+                    return None;
+                }
+                let next = self.find_visible(item.next, true, preview);
+                let prev = self.find_visible(item.prev, false, preview);
+
+                let (chapter_start, chapter_end) = self.chapter_bounds(*index);
+                let first = self.find_visible_until(Some(chapter_start), Some(chapter_end), true, preview);
+                let last = self.find_visible_until(Some(chapter_end), Some(chapter_start), false, preview);
+
+                let prev_chapter = match self.storage.items[chapter_start].prev {
+                    Some(prev_end) => {
+                        let (prev_start, _) = self.chapter_bounds(prev_end);
+                        self.find_visible_until(Some(prev_start), Some(prev_end), true, preview)
+                    },
                     None => None,
                 };
-                let prev = match item.prev {
-                    Some(index) => Some(&self.storage.items[index]),
+                let next_chapter = match self.storage.items[chapter_end].next {
+                    Some(next_start) => {
+                        let (_, next_end) = self.chapter_bounds(next_start);
+                        self.find_visible_until(Some(next_start), Some(next_end), true, preview)
+                    },
                     None => None,
                 };
+
                 Some(FoundIndex {
                     title: item.title.clone(),
                     slug: item.slug.clone(),
                     file: item.file.clone(),
+                    files: item.files(),
+                    publish_at: item.publish_at,
+                    series: item.series.clone(),
+                    chapter: item.chapter.clone(),
+                    tags: item.tags.clone(),
+                    transcript: item.transcript.clone(),
+                    hover_text: item.hover_text.clone(),
+                    commentary: item.commentary.clone(),
                     prev_slug: match prev {
                         Some(pi) => Some(pi.slug.clone()),
                         None => None,
@@ -138,35 +612,304 @@ impl Index {
                         Some(ni) => Some(ni.slug.clone()),
                         None => None,
                     },
+                    first_slug: first.map(|item| item.slug.clone()),
+                    last_slug: last.map(|item| item.slug.clone()),
+                    prev_chapter_slug: prev_chapter.map(|item| item.slug.clone()),
+                    next_chapter_slug: next_chapter.map(|item| item.slug.clone()),
+                    number: self.series_items(&item.series, preview)
+                        .take_while(|other| other.slug != item.slug)
+                        .count() + 1,
+                    count: self.series_items(&item.series, preview).count(),
                 })
             },
             None => None,
         }
     }
 
-    pub fn first_slug(&self) -> Option<String> {
-        if self.storage.items.len() > 0 {
-            Some(self.storage.items[0].slug.clone())
-        } else {
-            None
+    /// Returns current slug of the item that used to have the given slug.
+    pub fn canonical_slug(&self, alias: &str, preview: bool) -> Option<String> {
+        match self.storage.alias_map.get(alias) {
+            Some(index) => {
+                let item = &self.storage.items[*index];
+                if self.is_visible(item, preview) {
+                    Some(item.slug.clone())
+                } else {
+                    None
+                }
+            },
+            None => None,
         }
     }
 
-    pub fn last_slug(&self) -> Option<String> {
-        if self.storage.items.len() > 0 {
-            Some(self.storage.items[self.storage.items.len() - 1].slug.clone())
-        } else {
-            None
+    /// Returns permalink of the page, taking its series into account.
+    pub fn permalink(&self, slug: &str) -> String {
+        match self.storage.slug_map.get(slug) {
+            Some(index) => {
+                let item = &self.storage.items[*index];
+                page_url(item.series.as_ref().map(|s| s.as_ref()), &item.slug)
+            },
+            None => page_url(None, slug),
         }
     }
 
-    pub fn random_slug(&self) -> Option<String> {
-        if self.storage.items.len() > 0 {
-            let between = Range::new(0, self.storage.items.len());
-            let random_index = between.ind_sample(&mut rand::thread_rng());
-            Some(self.storage.items[random_index].slug.clone())
-        } else {
-            None
+    /// Returns all visible items in the order they were added.
+    pub fn entries(&self, preview: bool) -> Vec<Entry> {
+        self.storage.items.iter()
+            .filter(|item| self.is_visible(item, preview))
+            .map(|item| item.to_entry())
+            .collect()
+    }
+
+    /// Visible items of the series in reading order.
+    fn series_items<'a>(&'a self, series: &'a Option<String>, preview: bool) -> Box<Iterator<Item = &'a IndexItem> + 'a> {
+        Box::new(self.storage.items.iter()
+            .filter(move |item| item.series == *series && self.is_visible(item, preview)))
+    }
+
+    /// Returns visible page of the series by its number, starting from 1.
+    pub fn slug_by_number(&self, series: Option<&str>, number: usize, preview: bool) -> Option<String> {
+        if number == 0 {
+            return None;
+        }
+        let series = series.map(|s| s.to_string());
+        self.series_items(&series, preview)
+            .nth(number - 1)
+            .map(|item| item.slug.clone())
+    }
+
+    /// Returns first visible item published on the day, given as `2016-05-12`.
+    pub fn find_by_date(&self, date: &str, preview: bool) -> Option<String> {
+        self.storage.items.iter()
+            .filter(|item| self.is_visible(item, preview))
+            .find(|item| item.published_on().map(format_date) == Some(date.to_string()))
+            .map(|item| item.slug.clone())
+    }
+
+    /// Returns visible items carrying the tag, in the order they were added.
+    pub fn tagged_entries(&self, tag: &str, preview: bool) -> Vec<Entry> {
+        self.storage.items.iter()
+            .filter(|item| item.has_tag(Some(tag)) && self.is_visible(item, preview))
+            .map(|item| item.to_entry())
+            .collect()
+    }
+
+    /// Returns visible items matching the search query, best matches first.
+    pub fn search(&self, query: &str, preview: bool) -> Vec<Entry> {
+        self.storage.search.search(query).into_iter()
+            .map(|index| &self.storage.items[index])
+            .filter(|item| self.is_visible(item, preview))
+            .map(|item| item.to_entry())
+            .collect()
+    }
+
+    /// Returns the latest visible page of any series.
+    pub fn last_slug(&self, preview: bool) -> Option<String> {
+        self.storage.items.iter()
+            .rev()
+            .find(|item| self.is_visible(item, preview))
+            .map(|item| item.slug.clone())
+    }
+
+    /// Picks next visible page from visitor's shuffled order, only among pages
+    /// with the tag if it is given. Never returns the `current` page, and
+    /// nothing repeats until every page was shown.
+    pub fn random_slug(&self, preview: bool, tag: Option<&str>, current: Option<&str>, shuffle: &mut Shuffle)
+        -> Option<String>
+    {
+        let visible: Vec<&str> = self.storage.items.iter()
+            .filter(|item| item.has_tag(tag) && self.is_visible(item, preview))
+            .map(|item| &item.slug[..])
+            .collect();
+
+        shuffle.next(&visible, current).map(|slug| slug.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use index_models::IndexRepr;
+    use backend::json::JsonBackend;
+    use revisions::Revisions;
+    use super::{ Index, Storage };
+
+    fn repr(slug: &str, series: Option<&str>, chapter: Option<&str>) -> IndexRepr {
+        IndexRepr {
+            title: slug.to_uppercase(),
+            slug: slug.into(),
+            file: [slug, ".png"].concat(),
+            panels: None,
+            publish_at: None,
+            date: None,
+            aliases: None,
+            series: series.map(|s| s.into()),
+            chapter: chapter.map(|c| c.into()),
+            tags: None,
+            transcript: None,
+            hover_text: None,
+            commentary: None,
+        }
+    }
+
+    fn storage(pages: &[(&str, Option<&str>)]) -> Storage {
+        Storage::from_reprs(pages.iter()
+            .map(|&(slug, series)| repr(slug, series, None))
+            .collect()).unwrap()
+    }
+
+    /// Slugs of every item with slugs of the items it links to.
+    fn links(storage: &Storage) -> Vec<(&str, Option<&str>, Option<&str>)> {
+        for (index, item) in storage.items.iter().enumerate() {
+            assert_eq!(storage.slug_map.get(&item.slug), Some(&index));
+        }
+        assert_eq!(storage.slug_map.len(), storage.items.len());
+
+        storage.items.iter()
+            .map(|item| (
+                &item.slug[..],
+                item.prev.map(|i| &storage.items[i].slug[..]),
+                item.next.map(|i| &storage.items[i].slug[..]),
+            ))
+            .collect()
+    }
+
+    #[test]
+    fn removes_first_item() {
+        let mut storage = storage(&[("a", None), ("b", None), ("c", None)]);
+        assert_eq!(storage.remove("a").map(|item| item.slug), Some("a".into()));
+        assert_eq!(links(&storage), vec![
+            ("b", None, Some("c")),
+            ("c", Some("b"), None),
+        ]);
+    }
+
+    #[test]
+    fn removes_middle_item() {
+        let mut storage = storage(&[("a", None), ("b", None), ("c", None)]);
+        assert_eq!(storage.remove("b").map(|item| item.slug), Some("b".into()));
+        assert_eq!(links(&storage), vec![
+            ("a", None, Some("c")),
+            ("c", Some("a"), None),
+        ]);
+    }
+
+    #[test]
+    fn removes_last_item() {
+        let mut storage = storage(&[("a", None), ("b", None), ("c", None)]);
+        assert_eq!(storage.remove("c").map(|item| item.slug), Some("c".into()));
+        assert_eq!(links(&storage), vec![
+            ("a", None, Some("b")),
+            ("b", Some("a"), None),
+        ]);
+    }
+
+    #[test]
+    fn removes_item_of_second_series() {
+        let mut storage = storage(&[
+            ("a", None), ("x", Some("s")), ("b", None), ("y", Some("s")), ("z", Some("s")),
+        ]);
+        assert_eq!(links(&storage), vec![
+            ("a", None, Some("b")),
+            ("x", None, Some("y")),
+            ("b", Some("a"), None),
+            ("y", Some("x"), Some("z")),
+            ("z", Some("y"), None),
+        ]);
+
+        assert_eq!(storage.remove("y").map(|item| item.slug), Some("y".into()));
+        assert_eq!(links(&storage), vec![
+            ("a", None, Some("b")),
+            ("x", None, Some("z")),
+            ("b", Some("a"), None),
+            ("z", Some("x"), None),
+        ]);
+    }
+
+    #[test]
+    fn ignores_missing_item() {
+        let mut storage = storage(&[("a", None), ("b", None)]);
+        assert!(storage.remove("c").is_none());
+        assert_eq!(links(&storage), vec![
+            ("a", None, Some("b")),
+            ("b", Some("a"), None),
+        ]);
+    }
+
+    /// Two series with chapters, pages of both added in turns.
+    fn chapters() -> Index {
+        let pages = [
+            ("a1", None, "one"),
+            ("x1", Some("s"), "intro"),
+            ("a2", None, "one"),
+            ("b1", None, "two"),
+            ("x2", Some("s"), "intro"),
+            ("x3", Some("s"), "end"),
+            ("b2", None, "two"),
+            ("c1", None, "three"),
+        ];
+        Index {
+            backend: Box::new(JsonBackend::new(Path::new("index.json"))),
+            storage: Storage::from_reprs(pages.iter()
+                .map(|&(slug, series, chapter)| repr(slug, series, Some(chapter)))
+                .collect()).unwrap(),
+            revisions: Revisions::new(Path::new("revisions.jsonl")),
         }
     }
+
+    /// First, last, previous chapter and next chapter slugs of the page.
+    fn chapter_links(index: &Index, slug: &str) -> (String, String, Option<String>, Option<String>) {
+        let found = index.find(slug, false).unwrap();
+        (
+            found.first_slug.unwrap(),
+            found.last_slug.unwrap(),
+            found.prev_chapter_slug,
+            found.next_chapter_slug,
+        )
+    }
+
+    fn slugs(first: &str, last: &str, prev_chapter: Option<&str>, next_chapter: Option<&str>)
+        -> (String, String, Option<String>, Option<String>)
+    {
+        (first.into(), last.into(), prev_chapter.map(|s| s.into()), next_chapter.map(|s| s.into()))
+    }
+
+    #[test]
+    fn bounds_chapter_within_series() {
+        let index = chapters();
+        assert_eq!(index.chapter_bounds(0), (0, 2));
+        assert_eq!(index.chapter_bounds(2), (0, 2));
+        assert_eq!(index.chapter_bounds(3), (3, 6));
+        assert_eq!(index.chapter_bounds(6), (3, 6));
+        assert_eq!(index.chapter_bounds(7), (7, 7));
+        assert_eq!(index.chapter_bounds(1), (1, 4));
+        assert_eq!(index.chapter_bounds(5), (5, 5));
+    }
+
+    #[test]
+    fn finds_first_and_last_page_of_chapter() {
+        let index = chapters();
+        assert_eq!(chapter_links(&index, "a1"), slugs("a1", "a2", None, Some("b1")));
+        assert_eq!(chapter_links(&index, "a2"), slugs("a1", "a2", None, Some("b1")));
+        assert_eq!(chapter_links(&index, "b2"), slugs("b1", "b2", Some("a1"), Some("c1")));
+        assert_eq!(chapter_links(&index, "c1"), slugs("c1", "c1", Some("b1"), None));
+    }
+
+    #[test]
+    fn finds_chapters_of_second_series() {
+        let index = chapters();
+        assert_eq!(chapter_links(&index, "x2"), slugs("x1", "x2", None, Some("x3")));
+        assert_eq!(chapter_links(&index, "x3"), slugs("x3", "x3", Some("x1"), None));
+    }
+
+    #[test]
+    fn steps_across_chapters() {
+        let index = chapters();
+        let found = index.find("a2", false).unwrap();
+        assert_eq!(found.prev_slug, Some("a1".into()));
+        assert_eq!(found.next_slug, Some("b1".into()));
+
+        let found = index.find("x2", false).unwrap();
+        assert_eq!(found.prev_slug, Some("x1".into()));
+        assert_eq!(found.next_slug, Some("x3".into()));
+    }
 }
@@ -1,2 +1,3 @@
 pub mod response;
 pub mod not_found;
+pub mod multipart;
@@ -0,0 +1,262 @@
+use std::io::Read;
+use std::mem;
+use std::collections::HashMap;
+use iron::prelude::*;
+use hyper::header::ContentType;
+use hyper::mime::{Mime, TopLevel, SubLevel, Attr, Value};
+
+#[derive(Debug)]
+pub struct Part {
+    pub filename: Option<String>,
+    pub data: Vec<u8>,
+}
+
+impl Part {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.data).into_owned()
+    }
+}
+
+fn get_boundary(req: &Request) -> Option<String> {
+    match req.headers.get::<ContentType>() {
+        Some(&ContentType(Mime(TopLevel::Multipart, SubLevel::FormData, ref params))) => {
+            params.iter()
+                .filter_map(|&(ref attr, ref value)| match (attr, value) {
+                    (&Attr::Boundary, &Value::Ext(ref b)) => Some(b.trim_matches('"').to_string()),
+                    _ => None,
+                })
+                .next()
+        },
+        _ => None,
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8], from: usize) -> Option<usize> {
+    if needle.len() == 0 || haystack.len() < needle.len() {
+        return None;
+    }
+    (from..haystack.len() - needle.len() + 1)
+        .find(|&i| &haystack[i..i + needle.len()] == needle)
+}
+
+/// Splits header parameters on `;`, keeping quoted values, which may contain
+/// `;` and `=`, in one piece.
+fn split_params(header: &str) -> Vec<String> {
+    let mut params = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for c in header.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if quoted => {
+                current.push(c);
+                escaped = true;
+            },
+            '"' => {
+                current.push(c);
+                quoted = !quoted;
+            },
+            ';' if !quoted => params.push(mem::replace(&mut current, String::new())),
+            c => current.push(c),
+        }
+    }
+    params.push(current);
+
+    params
+}
+
+/// Removes quotes around the value and backslashes escaping quoted characters.
+fn unquote(value: &str) -> String {
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return value.to_string();
+    }
+
+    let mut result = String::new();
+    let mut escaped = false;
+    for c in value[1..value.len() - 1].chars() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            c => {
+                result.push(c);
+                escaped = false;
+            },
+        }
+    }
+    result
+}
+
+fn disposition_param(header: &str, key: &str) -> Option<String> {
+    split_params(header).iter()
+        .filter_map(|p| {
+            let mut kv = p.trim().splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some(k), Some(v)) if k.trim().to_lowercase() == key => Some(unquote(v.trim())),
+                _ => None,
+            }
+        })
+        .next()
+}
+
+fn parse_part(raw: &[u8]) -> Option<(String, Part)> {
+    let headers_end = match find(raw, b"\r\n\r\n", 0) {
+        Some(pos) => pos,
+        None => return None,
+    };
+
+    let headers = String::from_utf8_lossy(&raw[..headers_end]).into_owned();
+    let disposition = match headers.lines().find(|l| l.to_lowercase().starts_with("content-disposition:")) {
+        Some(line) => line["content-disposition:".len()..].to_string(),
+        None => return None,
+    };
+
+    let name = match disposition_param(&disposition, "name") {
+        Some(name) => name,
+        None => return None,
+    };
+
+    Some((name, Part {
+        filename: disposition_param(&disposition, "filename"),
+        data: raw[headers_end + 4..].to_vec(),
+    }))
+}
+
+/// Reads `multipart/form-data` request body and returns parts by field name.
+/// Bodies larger than `limit` bytes are rejected.
+pub fn parse(req: &mut Request, limit: u64) -> Option<HashMap<String, Part>> {
+    let boundary = match get_boundary(req) {
+        Some(b) => b,
+        None => return None,
+    };
+
+    let mut body = Vec::new();
+    if let Err(e) = req.body.by_ref().take(limit + 1).read_to_end(&mut body) {
+        println!("error reading multipart body: {:?}", e);
+        return None;
+    }
+    if body.len() as u64 > limit {
+        println!("multipart body is larger than {} bytes", limit);
+        return None;
+    }
+
+    parse_body(&body, &boundary)
+}
+
+/// Splits body into parts. Returns `None` if the body does not end with the
+/// closing boundary, which happens when the upload was cut short.
+fn parse_body(body: &[u8], boundary: &str) -> Option<HashMap<String, Part>> {
+    let boundary = ["--", boundary].concat().into_bytes();
+    let delimiter = [b"\r\n".as_ref(), boundary.as_ref()].concat();
+    let mut parts = HashMap::new();
+
+    let mut pos = match find(body, &boundary, 0) {
+        Some(pos) => pos + boundary.len(),
+        None => return None,
+    };
+
+    while body[pos..].starts_with(b"\r\n") {
+        let start = pos + 2;
+        let end = match find(body, &delimiter, start) {
+            Some(end) => end,
+            None => return None,
+        };
+
+        if let Some((name, part)) = parse_part(&body[start..end]) {
+            parts.insert(name, part);
+        }
+
+        pos = end + delimiter.len();
+    }
+
+    if !body[pos..].starts_with(b"--") {
+        return None;
+    }
+
+    Some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ parse_body, disposition_param };
+
+    fn body(parts: &[&str], end: &str) -> Vec<u8> {
+        let mut body = String::new();
+        for part in parts {
+            body.push_str("--xyz\r\n");
+            body.push_str(part);
+            body.push_str("\r\n");
+        }
+        body.push_str(end);
+        body.into_bytes()
+    }
+
+    #[test]
+    fn splits_parts_on_boundary() {
+        let body = body(&[
+            "Content-Disposition: form-data; name=\"title\"\r\n\r\nHello",
+            "Content-Disposition: form-data; name=\"file\"; filename=\"a.png\"\r\n\
+             Content-Type: image/png\r\n\r\n--xy\r\nz",
+        ], "--xyz--\r\n");
+
+        let parts = parse_body(&body, "xyz").unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts["title"].text(), "Hello");
+        assert_eq!(parts["title"].filename, None);
+        assert_eq!(parts["file"].data, b"--xy\r\nz".to_vec());
+        assert_eq!(parts["file"].filename, Some("a.png".to_string()));
+    }
+
+    #[test]
+    fn ignores_preamble() {
+        let mut with_preamble = b"preamble\r\n".to_vec();
+        with_preamble.extend(body(&["Content-Disposition: form-data; name=\"a\"\r\n\r\n1"], "--xyz--"));
+
+        let parts = parse_body(&with_preamble, "xyz").unwrap();
+        assert_eq!(parts["a"].text(), "1");
+    }
+
+    #[test]
+    fn rejects_missing_boundary() {
+        assert!(parse_body(b"no boundary here", "xyz").is_none());
+    }
+
+    #[test]
+    fn rejects_missing_final_boundary() {
+        let mut body = b"--xyz\r\n".to_vec();
+        body.extend(b"Content-Disposition: form-data; name=\"a\"\r\n\r\n1".iter().cloned());
+        assert!(parse_body(&body, "xyz").is_none());
+    }
+
+    #[test]
+    fn rejects_truncated_final_boundary() {
+        let body1 = body(&["Content-Disposition: form-data; name=\"a\"\r\n\r\n1"], "--xy");
+        assert!(parse_body(&body1, "xyz").is_none());
+
+        let body2 = body(&["Content-Disposition: form-data; name=\"a\"\r\n\r\n1"], "--xyz");
+        assert!(parse_body(&body2, "xyz").is_none());
+    }
+
+    #[test]
+    fn reads_quoted_params_with_separators() {
+        let header = " form-data; name=\"file\"; filename=\"a;b=c.png\"";
+        assert_eq!(disposition_param(header, "name"), Some("file".to_string()));
+        assert_eq!(disposition_param(header, "filename"), Some("a;b=c.png".to_string()));
+    }
+
+    #[test]
+    fn reads_escaped_quotes() {
+        let header = " form-data; name=\"file\"; filename=\"say \\\"hi\\\".png\"";
+        assert_eq!(disposition_param(header, "filename"), Some("say \"hi\".png".to_string()));
+    }
+
+    #[test]
+    fn reads_unquoted_params() {
+        assert_eq!(disposition_param(" form-data; name=title", "name"), Some("title".to_string()));
+        assert_eq!(disposition_param(" form-data; name=title", "filename"), None);
+    }
+}
@@ -16,17 +16,8 @@ impl NotFoundPage {
             path: template_path.into(),
         }
     }
-}
-
-impl typemap::Key for NotFoundPage { type Value = u64; }
-
-impl AfterMiddleware for NotFoundPage {
-    fn after(&self, _req: &mut Request, mut res: Response) -> IronResult<Response> {
-        match res.status {
-            Some(Status::NotFound) => (),
-            _ => return Ok(res),
-        }
 
+    fn render(&self, mut res: Response) -> Response {
         let parsed = template::parse(
             &self.path,
             &globals::Globals::new()
@@ -39,6 +30,25 @@ impl AfterMiddleware for NotFoundPage {
             )
         );
 
-        Ok(res)
+        res
+    }
+}
+
+impl typemap::Key for NotFoundPage { type Value = u64; }
+
+impl AfterMiddleware for NotFoundPage {
+    fn after(&self, _req: &mut Request, res: Response) -> IronResult<Response> {
+        match res.status {
+            Some(Status::NotFound) => Ok(self.render(res)),
+            _ => Ok(res),
+        }
+    }
+
+    /// Routers answer unknown paths with an error, which skips `after`.
+    fn catch(&self, _req: &mut Request, err: IronError) -> IronResult<Response> {
+        match err.response.status {
+            Some(Status::NotFound) => Ok(self.render(err.response)),
+            _ => Err(err),
+        }
     }
 }
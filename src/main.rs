@@ -11,8 +11,20 @@ extern crate rand;
 extern crate image;
 extern crate url;
 extern crate cookie;
+extern crate pulldown_cmark;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
 
 mod index;
+mod backend;
+mod errors;
+mod archive;
+mod calendar;
+mod feed;
+mod sitemap;
+mod search;
+mod shuffle;
+mod commentary;
 mod template;
 mod globals;
 mod iron_ex;
@@ -23,103 +35,32 @@ mod resizer;
 mod resizer_models;
 mod users;
 mod users_models;
+mod api;
+mod api_models;
+mod revisions;
+mod revisions_models;
+mod check;
+mod importer;
+mod migrate;
+mod watcher;
+mod handlers;
 
 use iron::prelude::*;
-use iron::status;
-use iron::method;
-use std::convert::AsRef;
 use std::collections::HashMap;
-
-use hyper::header::{CacheControl, CacheDirective};
-use hyper::header::Location;
-use hyper::header::{ContentType, SetCookie, Cookie};
-use cookie::Cookie as CookiePair;
-use hyper::mime::{Mime, TopLevel, SubLevel, Attr, Value};
-
+use std::env;
 use std::path::Path;
 use std::sync::Mutex;
 use std::sync::Arc;
+use std::time::Duration;
+use std::process;
 use mount::Mount;
 use staticfile::Static;
 use router::Router;
-use resizer::{ Resizer, ResizeMode, SizeHint };
+use resizer::Resizer;
+use commentary::Commentary;
+use handlers::{ App, route, pages, listings, admin };
 use rand::distributions::{IndependentSample, Range};
 
-fn send_page(index: &mut index::Index, resizer: &mut Resizer, req: &mut Request, cookie: &str) -> IronResult<Response> {
-    match req.extensions.get::<Router>()
-        .unwrap().find("slug") {
-            Some(ref slug) => {
-                match index.find(slug) {
-                    Some(found) => {
-                        let admin_access = check_admin(req, cookie);
-
-                        let image_url = match resizer.get_resized_url(
-                            &found.file,
-                            ResizeMode::Fit(
-                                SizeHint { w: Some(1000), h: None }
-                            ))
-                        {
-                            Some(i) => i.relative_url,
-                            None => "".to_string(),
-                        };
-
-                        let mut vals = globals::Globals::new()
-                            .with("title", found.title)
-                            .with("file", ["/ic/", image_url.as_ref()].concat())
-                            .with("width", "".into())
-                            .with("height", "".into());
-
-                        if admin_access {
-                            let parsed = template::parse(
-                                "views/admin/controls.html",
-                                &vals
-                            );
-                            vals.amend("admin_controls", String::from_utf8_lossy(&parsed).into_owned());
-                        } else {
-                            vals.amend("admin_controls", String::new());
-                        }
-
-                        append_link(&mut vals, "first_disabled", "first_href", match index.first_slug() {
-                            Some(slug) => if slug == found.slug {
-                                None
-                            } else {
-                                Some(["/c/", slug.as_ref()].concat())
-                            },
-                            None => None,
-                        });
-
-                        append_link(&mut vals, "prev_disabled", "prev_href", match found.prev_slug {
-                            Some(slug) => Some(["/c/", slug.as_ref()].concat()),
-                            None => None,
-                        });
-
-                        append_link(&mut vals, "random_disabled", "random_href", Some(
-                            "/random".into()
-                        ));
-
-                        append_link(&mut vals, "next_disabled", "next_href", match found.next_slug {
-                            Some(slug) => Some(["/c/", slug.as_ref()].concat()),
-                            None => None,
-                        });
-
-                        append_link(&mut vals, "last_disabled", "last_href", match index.last_slug() {
-                            Some(slug) => if slug == found.slug {
-                                None
-                            } else {
-                                Some(["/c/", slug.as_ref()].concat())
-                            },
-                            None => None,
-                        });
-
-                        Ok(view("views/comic.html", vals))
-                    },
-                    None => Ok(Response::with(status::NotFound)),
-                }
-            },
-            None => Ok(Response::with(status::NotFound)),
-        }
-}
-
 static SYMBOLS: &'static [u8] = b"abcdefghijklmnopqrstuvyzABCDEFGHIJKLMNOPQRSTUVYZ1234567890";
 
 fn random_str(len: u32) -> String {
@@ -134,130 +75,127 @@ fn random_str(len: u32) -> String {
     s
 }
 
+/// Reports error that prevents server from starting and exits.
+fn fatal(e: &errors::LoadError) -> ! {
+    println!("fatal: {}", e);
+    process::exit(1);
+}
+
 fn main() {
-    // our "database", simply load from json file.
-    let index = Arc::new(Mutex::new(index::Index::from_file("data/index.json")));
-    let resizer = Mutex::new(Resizer::new(Path::new("data/images"), Path::new("cache/images")));
-    let admin_cookie = random_str(120);
-    let users = Mutex::new(users::Users::from_file("config/users.json").expect("failed to load users"));
+    let index_path = env::var("COMICS_INDEX").unwrap_or("data/index.json".into());
+
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|a| &a[..]) {
+        Some("check") => {
+            let ok = check::run(Path::new(&index_path), Path::new("data/images"));
+            process::exit(if ok { 0 } else { 1 });
+        },
+        Some("migrate") => {
+            // copies index from another file into COMICS_INDEX, e.g. json to sqlite
+            let ok = match args.get(2) {
+                Some(from) => migrate::run(Path::new(from), Path::new(&index_path)),
+                None => {
+                    println!("usage: migrate <from index path>");
+                    false
+                }
+            };
+            process::exit(if ok { 0 } else { 1 });
+        },
+        Some("import") => {
+            let dry_run = args.iter().any(|a| a == "--dry-run");
+            let dir = args.iter().skip(2).find(|a| !a.starts_with("--")).map(|a| &a[..]).unwrap_or("data/images");
+            let ok = match index::Index::load(Path::new(&index_path)) {
+                Some(mut index) => importer::run(&mut index, Path::new("data/images"), Path::new(dir), dry_run),
+                None => false,
+            };
+            process::exit(if ok { 0 } else { 1 });
+        },
+        _ => (),
+    }
+
+    // our "database", json file by default, or .sqlite file with "sqlite" feature.
+    let index = match index::Index::open(Path::new(&index_path)) {
+        Ok(index) => Arc::new(Mutex::new(index)),
+        Err(e) => fatal(&e),
+    };
+    watcher::watch(index.clone(), Duration::from_secs(2));
+    let users = match users::Users::from_file(Path::new("config/users.json")) {
+        Ok(users) => Mutex::new(users),
+        Err(e) => fatal(&e),
+    };
+
+    let app = Arc::new(App {
+        index: index,
+        resizer: Mutex::new(Resizer::new(Path::new("data/images"), Path::new("cache/images"))),
+        commentary: Mutex::new(Commentary::new(Path::new("data/images"))),
+        users: users,
+        sessions: Mutex::new(HashMap::new()),
+    });
 
-    let index_for_pages = index.clone();
-    let cookie_for_page = admin_cookie.clone();
     let mut router = Router::new();
     router
-        .get("/:slug", move |req: &mut Request| -> IronResult<Response> {
-            match (index_for_pages.lock(), resizer.lock()) {
-                (Ok(mut index), Ok(mut resizer)) => {
-                    send_page(&mut index, &mut resizer, req, &cookie_for_page)
-                },
-                _ => {
-                    println!("Error locking index or resizer");
-                    Ok(Response::with(status::NotFound))
-                }
-            }
-        });
+        .get("/:slug", route(&app, pages::page))
+        .get("/:series/:slug", route(&app, pages::page));
+
+    let mut admin_router = Router::new();
+    admin_router
+        .get("/upload", route(&app, admin::upload_form))
+        .post("/upload", route(&app, admin::upload))
+        .get("/delete/:slug", route(&app, admin::delete_form))
+        .post("/delete/:slug", route(&app, admin::delete))
+        .get("/revisions", route(&app, admin::revisions))
+        .post("/revisions/:id/:scope", route(&app, admin::restore));
+
+    let mut api_router = Router::new();
+    api_router
+        .get("/", route(&app, listings::api_list))
+        .get("/:slug", route(&app, listings::api_comic));
+
+    let mut tag_router = Router::new();
+    tag_router
+        .get("/:name", route(&app, listings::tag));
+
+    let mut home_router = Router::new();
+    home_router
+        .get("/", route(&app, pages::latest))
+        .get("/:year/:month/:day", route(&app, pages::by_date))
+        .get("/*", route(&app, pages::latest));
+
+    let mut number_router = Router::new();
+    number_router
+        .get("/", route(&app, pages::jump))
+        .get("/:number", route(&app, pages::numbered))
+        .get("/:series/:number", route(&app, pages::numbered));
+
+    let mut calendar_router = Router::new();
+    calendar_router
+        .get("/", route(&app, listings::latest_month))
+        .get("/:year/:month", route(&app, listings::calendar));
 
     let mut mount = Mount::new();
-    let index_for_first_page = index.clone();
-    let index_for_random = index.clone();
     mount
-        .mount("/", move |_req: &mut Request| -> IronResult<Response> {
-            match index_for_first_page.lock() {
-                Ok(index) => {
-                    match index.last_slug() {
-                        Some(slug) => {
-                            Ok(redirect(["/c/", slug.as_ref()].concat(), status::SeeOther))
-                        },
-                        None => {
-                            println!("No pages exist");
-                            Ok(Response::with(status::NotFound))
-                        }
-                    }
-                },
-                Err(e) => {
-                    println!("Error locking index: {:?}", e);
-                    Ok(Response::with(status::NotFound))
-                }
-            }
-        })
+        .mount("/", home_router)
+        .mount("/calendar", calendar_router)
+        .mount("/resume", route(&app, pages::resume))
         .mount("/c/", router)
-        // .mount("/admin", admin_router)
+        .mount("/p/", number_router)
+        .mount("/api/comics", api_router)
+        .mount("/tag/", tag_router)
+        .mount("/admin", admin_router)
         .mount("/favicon.png", Static::new(Path::new("public/favicon.png")))
         .mount("/ic/", Static::new(Path::new("cache/images")))
         .mount("/css/", Static::new(Path::new("public/css")))
         .mount("/font/", Static::new(Path::new("public/font")))
         .mount("/i/", Static::new(Path::new("data/images")))
-        .mount("/random", move |_req: &mut Request| -> IronResult<Response> {
-            match index_for_random.lock() {
-                Ok(index) => {
-                    match index.random_slug() {
-                        Some(slug) => {
-                            Ok(redirect(["/c/", slug.as_ref()].concat(), status::SeeOther))
-                        },
-                        None => {
-                            println!("No pages exist");
-                            Ok(Response::with(status::NotFound))
-                        },
-                    }
-                },
-                Err(e) => {
-                    println!("Error locking index: {:?}", e);
-                    Ok(Response::with(status::NotFound))
-                }
-            }
-        })
-        .mount("/login", move |req: &mut Request| -> IronResult<Response> {
-            println!("request method {:?}", req.method);
-            match req.method {
-                method::Method::Get => {
-                    let mut vals = globals::Globals::new();
-
-                    vals.amend("message", match req.url.query {
-                        Some(_) => "<span class=\"error\">Enter valid username and password.</span>".into(),
-                        None => "".into(),
-                    });
-
-                    Ok(view("views/login.html", vals))
-                },
-                _ => {
-                    Ok(Response::with(status::NotFound))
-                }
-            }
-        })
-        .mount("/login-post", move |req: &mut Request| -> IronResult<Response> {
-            println!("request method {:?}", req.method);
-            let success = match req.url.query {
-                Some(ref query) => {
-                    let items = url::form_urlencoded::parse(query.as_bytes())
-                        .into_iter()
-                        .collect::<HashMap<_, _>>();
-
-                    match (items.get("username").map(|v| v.as_ref()), items.get("password").map(|v| v.as_ref())) {
-                        (Some(username), Some(pass)) => match users.lock() {
-                            Ok(u) => u.authorize(username, pass),
-                            _ => false,
-                        },
-                        _ => false,
-                    }
-                },
-                None => false,
-            };
-
-            if success {
-                let mut response = redirect("/".into(), status::SeeOther);
-                let mut cookie_pair = CookiePair::new("session".to_owned(), admin_cookie.to_owned());
-                cookie_pair.expires = Some(time::now_utc() + time::Duration::hours(1));
-                response.headers.set(
-                   SetCookie(vec![
-                       cookie_pair
-                   ])
-                );
-
-                return Ok(response);
-            }
-
-            Ok(redirect("/login/?invalid".into(), status::SeeOther))
-        })
+        .mount("/random", route(&app, pages::random))
+        .mount("/feed.rss", route(&app, listings::rss))
+        .mount("/feed.atom", route(&app, listings::atom))
+        .mount("/sitemap.xml", route(&app, listings::sitemap))
+        .mount("/robots.txt", route(&app, listings::robots))
+        .mount("/search", route(&app, listings::search))
+        .mount("/archive", route(&app, listings::archive))
+        .mount("/login", route(&app, admin::login))
+        .mount("/login-post", route(&app, admin::login_post))
     ;
 
     let mut chain = Chain::new(mount);
@@ -268,69 +206,6 @@ fn main() {
     Iron::new(chain).http("localhost:3000").unwrap();
 }
 
-fn redirect(url: String, status: status::Status) -> Response {
-    let mut response = Response::with(status);
-    response.headers.set(
-        Location(url)
-    );
-    response.headers.set(
-        CacheControl(vec![
-            CacheDirective::NoCache,
-        ])
-    );
-    response
-}
-
-fn view(path: &str, vals: globals::Globals) -> Response {
-    let parsed = template::parse(
-        path,
-        &vals
-    );
-    let mut response = Response::with((status::Ok, parsed));
-    response.headers.set(
-        ContentType(
-            Mime(TopLevel::Text, SubLevel::Html, vec![(Attr::Charset, Value::Utf8)])
-        )
-    );
-    response
-}
-
-fn append_link(
-    vals: &mut globals::Globals,
-    disabled_key: &'static str,
-    href_key: &'static str,
-    href: Option<String>
-) {
-    vals.amend(disabled_key, match href {
-        None => "disabled".into(),
-        _ => "".into(),
-    });
-    vals.amend(href_key, match href {
-        Some(href) => href,
-        None => "javascript:;".into(),
-    });
-}
-
-fn check_admin(req: &Request, required_cookie: &str) -> bool {
-    match req.headers.get::<Cookie>() {
-        Some(&Cookie(ref vals)) => {
-            if vals.len() == 0 {
-                false
-            } else {
-                let maybe_cookie = vals.iter().find(|v| match *v {
-                    &CookiePair { ref name, .. } if name == "session" => true,
-                    _ => false,
-                });
-                match maybe_cookie {
-                    Some(&CookiePair { ref value, .. }) if value == required_cookie => true,
-                    _ => false,
-                }
-            }
-        },
-        _ => false,
-    }
-}
-
 #[cfg(feature = "prod")]
 fn enable_browser_cache(mut chain: Chain) -> Chain {
     chain.link_after(headers::StaticHeaders);
@@ -0,0 +1,51 @@
+use std::path::Path;
+
+use backend;
+
+/// Copies all records from index at `from` into index at `to`, for example
+/// from `data/index.json` to `data/index.sqlite`. Refuses to write into an
+/// index that already has records. Returns `false` on errors.
+pub fn run(from: &Path, to: &Path) -> bool {
+    if !from.exists() {
+        println!("index {:?} does not exist", from);
+        return false;
+    }
+
+    let items = match backend::open(from).and_then(|b| b.list()) {
+        Ok(items) => items,
+        Err(e) => {
+            println!("{}", e);
+            return false;
+        }
+    };
+
+    let mut target = match backend::open(to) {
+        Ok(target) => target,
+        Err(e) => {
+            println!("{}", e);
+            return false;
+        }
+    };
+
+    match target.list() {
+        Ok(ref existing) if existing.len() > 0 => {
+            println!("index {:?} already has {} pages, not migrating", to, existing.len());
+            return false;
+        },
+        Ok(_) => (),
+        Err(e) => {
+            println!("{}", e);
+            return false;
+        }
+    }
+
+    for item in &items {
+        if let Err(e) = target.insert(item) {
+            println!("{}", e);
+            return false;
+        }
+    }
+
+    println!("copied {} pages from {:?} to {:?}", items.len(), from, to);
+    true
+}
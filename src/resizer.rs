@@ -1,5 +1,6 @@
 use std::path::{ Path, PathBuf };
 use std::collections::HashMap;
+use std::cmp;
 use std::fs;
 use std::fs::File;
 use std::io::{ Read, Write };
@@ -15,6 +16,16 @@ pub enum ResizeMode {
     Fill(Size),
 }
 
+/// Size images are shown at on comic pages.
+pub fn page_mode() -> ResizeMode {
+    ResizeMode::Fit(SizeHint { w: Some(1000), h: None })
+}
+
+/// Fixed box for thumbnails in listings.
+pub fn thumbnail_mode() -> ResizeMode {
+    ResizeMode::Fill(Size { w: 160, h: 120 })
+}
+
 #[derive(Debug)]
 pub struct SizeHint {
     pub w: Option<u32>,
@@ -71,7 +82,39 @@ pub fn get_required_size(o: Size, mode: &ResizeMode) -> Result<Size, ()> {
             SizeHint { w: Some(w), h: Some(h) } => Ok(Size { w: w, h: h }),
             SizeHint { w: None, h: None } => Ok(Size { w: o.w, h: o.h }),
         },
-        _ => unimplemented!(),
+        ResizeMode::Fill(size) => Ok(size),
+    }
+}
+
+/// Scales image to cover the whole box and crops the overflow around center.
+fn fill(image: &DynamicImage, size: Size) -> DynamicImage {
+    let (w, h) = (image.width(), image.height());
+    let scale = f32::max(size.w as f32 / w as f32, size.h as f32 / h as f32);
+    let scaled_w = cmp::max(size.w, (w as f32 * scale).ceil() as u32);
+    let scaled_h = cmp::max(size.h, (h as f32 * scale).ceil() as u32);
+
+    let mut scaled = image.resize_exact(scaled_w, scaled_h, image::FilterType::Lanczos3);
+    scaled.crop((scaled_w - size.w) / 2, (scaled_h - size.h) / 2, size.w, size.h)
+}
+
+/// Checks if the part of cached file name after the original stem was
+/// produced by resizer: a copy, a `WxH.png` resize, a `WxHc.png` crop,
+/// or size cache.
+fn is_derived_suffix(suffix: &str, extension: &str) -> bool {
+    if suffix == extension || suffix == "size.json" {
+        return true;
+    }
+
+    let size = match suffix.rfind(".png") {
+        Some(pos) if pos + 4 == suffix.len() => &suffix[..pos],
+        _ => return false,
+    };
+
+    let size = size.trim_right_matches('c');
+    let mut dimensions = size.split('x');
+    match (dimensions.next(), dimensions.next(), dimensions.next()) {
+        (Some(w), Some(h), None) => w.parse::<u32>().is_ok() && h.parse::<u32>().is_ok(),
+        _ => false,
     }
 }
 
@@ -173,6 +216,44 @@ impl Resizer {
         }
     }
 
+    /// Forgets cached size and removes all resized copies of the image.
+    pub fn purge(&mut self, url: &str) {
+        let path = &self.root_path.join(url);
+        let filecached_path = &self.get_filecache_path(path);
+
+        self.set_memcached_size(path, None);
+        self.set_filecached_size(filecached_path, None);
+
+        let original = Path::new(url);
+        let (stem, extension) = match (original.file_stem(), original.extension()) {
+            (Some(stem), Some(extension)) => (
+                stem.to_string_lossy().into_owned(),
+                extension.to_string_lossy().into_owned()
+            ),
+            _ => return,
+        };
+        let prefix = [stem.as_ref(), "."].concat();
+
+        let list = match fs::read_dir(&self.resize_cache) {
+            Ok(list) => list,
+            Err(e) => {
+                println!("error reading resize cache {:?}: {:?}", self.resize_cache, e);
+                return;
+            },
+        };
+
+        for maybe_entry in list {
+            if let Ok(entry) = maybe_entry {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                if name.starts_with(&prefix) && is_derived_suffix(&name[prefix.len()..], &extension) {
+                    if let Err(e) = fs::remove_file(entry.path()) {
+                        println!("error removing cached image {:?}: {:?}", entry.path(), e);
+                    }
+                }
+            }
+        }
+    }
+
     pub fn get_resized_url<'r>(&mut self, url: &'r str, mode: ResizeMode) -> Option<ResizeResult> {
         let path = &self.root_path.join(url);
         let filecached_path = &self.get_filecache_path(path);
@@ -198,9 +279,16 @@ impl Resizer {
             }
         };
 
+        let crop = match mode {
+            ResizeMode::Fill(_) => true,
+            _ => false,
+        };
+
         let size_str = [required_size.w.to_string().as_ref(), required_size.h.to_string().as_ref()].connect("x");
         let (extension, needs_resize) = if required_size.w == original_size.w && required_size.h == original_size.h {
             (Path::new(url).extension().unwrap().to_string_lossy().into_owned(), false)
+        } else if crop {
+            ([size_str.as_ref(), "c.png"].concat(), true)
         } else {
             ([size_str.as_ref(), ".png"].concat(), true)
         };
@@ -221,8 +309,12 @@ impl Resizer {
                             },
                         };
                     }
-                    let new_image = image.unwrap()
-                        .resize_exact(required_size.w, required_size.h, image::FilterType::Lanczos3);
+                    let new_image = if crop {
+                        fill(&image.unwrap(), required_size)
+                    } else {
+                        image.unwrap()
+                            .resize_exact(required_size.w, required_size.h, image::FilterType::Lanczos3)
+                    };
 
                     match new_image.save(fout, image::PNG) {
                         Err(e) => {
@@ -244,3 +336,27 @@ impl Resizer {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::is_derived_suffix;
+
+    #[test]
+    fn accepts_derived_files() {
+        assert!(is_derived_suffix("png", "png"));
+        assert!(is_derived_suffix("size.json", "png"));
+        assert!(is_derived_suffix("800x600.png", "jpg"));
+        assert!(is_derived_suffix("200x200c.png", "jpg"));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(!is_derived_suffix("jpg", "png"));
+        assert!(!is_derived_suffix("800x600.jpg", "jpg"));
+        assert!(!is_derived_suffix("800.png", "png"));
+        assert!(!is_derived_suffix("800x600x2.png", "png"));
+        assert!(!is_derived_suffix("wide-800x600.png", "png"));
+        assert!(!is_derived_suffix("2.png", "png"));
+        assert!(!is_derived_suffix("", "png"));
+    }
+}
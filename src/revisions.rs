@@ -0,0 +1,126 @@
+use std::fs;
+use std::fs::{ File, OpenOptions };
+use std::io::{ Read, Write };
+use std::path::{ Path, PathBuf };
+use serde_json;
+use time;
+
+use index;
+use index_models::IndexRepr;
+use revisions_models::RevisionRepr;
+use errors::LoadError;
+use globals::Globals;
+use template::{ escape, partial };
+
+/// Append-only log of page changes, one JSON record per line. Revisions are
+/// numbered by their line, starting from 1.
+pub struct Revisions {
+    path: PathBuf,
+}
+
+/// Places revision log next to the index file.
+pub fn log_path(index_path: &Path) -> PathBuf {
+    index_path.with_file_name("revisions.jsonl")
+}
+
+impl Revisions {
+    pub fn new(path: &Path) -> Revisions {
+        Revisions {
+            path: path.into(),
+        }
+    }
+
+    /// Returns all revisions, oldest first. Missing file is an empty log.
+    pub fn list(&self) -> Result<Vec<RevisionRepr>, LoadError> {
+        if let Err(_) = fs::metadata(&self.path) {
+            return Ok(Vec::new());
+        }
+
+        let mut contents = String::new();
+        let read = File::open(&self.path).and_then(|mut f| f.read_to_string(&mut contents));
+        if let Err(e) = read {
+            return Err(LoadError::Io { path: self.path.clone(), error: e });
+        }
+
+        let mut revisions = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            if line.trim().len() == 0 {
+                continue;
+            }
+            match serde_json::from_str(line) {
+                Ok(revision) => revisions.push(revision),
+                Err(e) => return Err(match LoadError::from_json(&self.path, e) {
+                    LoadError::Syntax { path, column, message, .. } => LoadError::Syntax {
+                        path: path,
+                        line: number + 1,
+                        column: column,
+                        message: message,
+                    },
+                    other => other,
+                }),
+            }
+        }
+
+        Ok(revisions)
+    }
+
+    /// Records change of a single page.
+    pub fn append(&self, user: &str, before: Option<IndexRepr>, after: Option<IndexRepr>) -> Result<(), LoadError> {
+        let slug = match (&before, &after) {
+            (_, &Some(ref repr)) => repr.slug.clone(),
+            (&Some(ref repr), _) => repr.slug.clone(),
+            _ => return Ok(()),
+        };
+
+        let revision = RevisionRepr {
+            user: user.into(),
+            time: index::format_time(time::get_time()),
+            slug: slug,
+            before: before,
+            after: after,
+        };
+
+        let serialized = match serde_json::to_string(&revision) {
+            Ok(serialized) => serialized,
+            Err(e) => return Err(LoadError::Invalid {
+                path: self.path.clone(),
+                message: format!("error serializing revision {:?}", e),
+            }),
+        };
+
+        let written = OpenOptions::new().create(true).append(true).open(&self.path)
+            .and_then(|mut f| f.write_all([serialized.as_ref(), "\n"].concat().as_bytes()).and_then(|_| f.sync_all()));
+
+        match written {
+            Ok(_) => Ok(()),
+            Err(e) => Err(LoadError::Write { path: self.path.clone(), error: e }),
+        }
+    }
+}
+
+/// Renders table rows of revisions, newest first, with restore buttons
+/// posting `csrf` token.
+pub fn render(revisions: &[RevisionRepr], csrf: &str) -> String {
+    let mut rows = String::new();
+
+    for (number, revision) in revisions.iter().enumerate().rev() {
+        let id = number + 1;
+        let (action, repr) = match (&revision.before, &revision.after) {
+            (&None, &Some(ref after)) => ("added", after),
+            (&Some(ref before), &None) => ("removed", before),
+            (_, &Some(ref after)) => ("changed", after),
+            _ => continue,
+        };
+
+        rows.push_str(&partial("views/admin/revision_row.html", &Globals::new()
+            .with("id", id.to_string())
+            .with("time", escape(&revision.time))
+            .with("user", escape(&revision.user))
+            .with("action", action.into())
+            .with("url", escape(&index::page_url(repr.series.as_ref().map(|s| &s[..]), &repr.slug)))
+            .with("title", escape(&repr.title))
+            .with("csrf", escape(csrf))));
+    }
+
+    rows
+}
@@ -0,0 +1,75 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Minimal query term length to also match longer words starting with it.
+const PREFIX_MIN_LEN: usize = 3;
+
+/// Inverted index from lowercase words to offsets of index items.
+#[derive(Debug)]
+pub struct SearchIndex {
+    words: HashMap<String, Vec<usize>>,
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() > 0)
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+impl SearchIndex {
+    pub fn new() -> SearchIndex {
+        SearchIndex {
+            words: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.words.clear();
+    }
+
+    pub fn add(&mut self, offset: usize, text: &str) {
+        for word in tokenize(text) {
+            let offsets = self.words.entry(word).or_insert(Vec::new());
+            if !offsets.contains(&offset) {
+                offsets.push(offset);
+            }
+        }
+    }
+
+    /// Returns offsets of items matching any query term, the ones matching
+    /// most terms first, newer items first among equal.
+    pub fn search(&self, query: &str) -> Vec<usize> {
+        let mut scores: HashMap<usize, usize> = HashMap::new();
+
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+
+        for term in &terms {
+            let mut matched: Vec<usize> = Vec::new();
+            for (word, offsets) in &self.words {
+                let matches = if term.len() >= PREFIX_MIN_LEN {
+                    word.starts_with(&term[..])
+                } else {
+                    word == term
+                };
+                if matches {
+                    matched.extend(offsets.iter().cloned());
+                }
+            }
+            matched.sort();
+            matched.dedup();
+            for offset in matched {
+                *scores.entry(offset).or_insert(0) += 1;
+            }
+        }
+
+        let mut results: Vec<(usize, usize)> = scores.into_iter().collect();
+        results.sort_by(|a, b| match b.1.cmp(&a.1) {
+            Ordering::Equal => b.0.cmp(&a.0),
+            other => other,
+        });
+        results.into_iter().map(|(offset, _)| offset).collect()
+    }
+}
@@ -0,0 +1,178 @@
+use rand;
+
+/// Position of a visitor in a shuffled order of pages, small enough to be
+/// kept in a cookie as `seed` or `seed.rank`. Every page gets a rank derived
+/// from the seed and its slug, and pages are visited by increasing rank, so
+/// pages added later fall into the order without starting it over.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Shuffle {
+    seed: u32,
+    last: Option<u32>,
+}
+
+/// FNV-1a hash, stable between restarts.
+fn hash(start: u32, bytes: &[u8]) -> u32 {
+    let mut hash = start;
+    for byte in bytes {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(16777619);
+    }
+    hash
+}
+
+fn rank(seed: u32, slug: &str) -> u32 {
+    let seed_bytes = [seed as u8, (seed >> 8) as u8, (seed >> 16) as u8, (seed >> 24) as u8];
+    hash(hash(2166136261, &seed_bytes), slug.as_bytes())
+}
+
+/// Name of the cookie keeping the order of pages with `tag`, so browsing
+/// one tag does not start the order of another over.
+pub fn cookie_name(tag: Option<&str>) -> String {
+    match tag {
+        Some(tag) => format!("random-{:08x}", hash(2166136261, tag.as_bytes())),
+        None => "random".into(),
+    }
+}
+
+impl Shuffle {
+    pub fn new() -> Shuffle {
+        Shuffle {
+            seed: rand::random(),
+            last: None,
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Shuffle> {
+        let mut parts = value.split('.');
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(seed), None, None) => seed.parse().ok().map(|seed| Shuffle {
+                seed: seed,
+                last: None,
+            }),
+            (Some(seed), Some(last), None) => match (seed.parse(), last.parse()) {
+                (Ok(seed), Ok(last)) => Some(Shuffle {
+                    seed: seed,
+                    last: Some(last),
+                }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn to_cookie(&self) -> String {
+        match self.last {
+            Some(last) => format!("{}.{}", self.seed, last),
+            None => format!("{}", self.seed),
+        }
+    }
+
+    /// Returns next slug in shuffled order, skipping `current`. Starts a new
+    /// order when every page was already seen. Returns `current` if it is
+    /// the only page.
+    pub fn next<'a>(&mut self, slugs: &[&'a str], current: Option<&str>) -> Option<&'a str> {
+        for _ in 0..2 {
+            let seed = self.seed;
+            let last = self.last;
+            let found = slugs.iter()
+                .cloned()
+                .filter(|&slug| Some(slug) != current)
+                .map(|slug| (rank(seed, slug), slug))
+                .filter(|&(rank, _)| last.map_or(true, |last| rank > last))
+                .min();
+
+            match found {
+                Some((rank, slug)) => {
+                    self.last = Some(rank);
+                    return Some(slug);
+                },
+                None => *self = Shuffle::new(),
+            }
+        }
+
+        slugs.iter().cloned().find(|&slug| Some(slug) == current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ Shuffle, cookie_name };
+
+    const SLUGS: &'static [&'static str] = &["a", "b", "c", "d", "e"];
+
+    fn walk(shuffle: &mut Shuffle, slugs: &[&'static str], count: usize) -> Vec<&'static str> {
+        (0..count).map(|_| shuffle.next(slugs, None).unwrap()).collect()
+    }
+
+    #[test]
+    fn visits_every_page_once_per_round() {
+        let mut shuffle = Shuffle::parse("7").unwrap();
+        let mut seen = walk(&mut shuffle, SLUGS, SLUGS.len());
+        seen.sort();
+        assert_eq!(seen, SLUGS.to_vec());
+    }
+
+    #[test]
+    fn continues_from_cookie() {
+        let mut shuffle = Shuffle::parse("7").unwrap();
+        let all = walk(&mut shuffle, SLUGS, 4);
+
+        let mut restored = Shuffle::parse("7").unwrap();
+        walk(&mut restored, SLUGS, 2);
+        let mut restored = Shuffle::parse(&restored.to_cookie()).unwrap();
+        assert_eq!(walk(&mut restored, SLUGS, 2), all[2..].to_vec());
+    }
+
+    #[test]
+    fn skips_current_page() {
+        let mut shuffle = Shuffle::parse("7").unwrap();
+        let first = shuffle.next(SLUGS, None).unwrap();
+
+        let mut again = Shuffle::parse("7").unwrap();
+        assert!(again.next(SLUGS, Some(first)) != Some(first));
+    }
+
+    #[test]
+    fn returns_current_when_it_is_the_only_page() {
+        let mut shuffle = Shuffle::parse("7").unwrap();
+        assert_eq!(shuffle.next(&["a"], Some("a")), Some("a"));
+        assert_eq!(shuffle.next(&["a"], Some("a")), Some("a"));
+    }
+
+    #[test]
+    fn returns_none_without_pages() {
+        let mut shuffle = Shuffle::parse("7").unwrap();
+        assert_eq!(shuffle.next(&[], None), None);
+        assert_eq!(shuffle.next(&[], Some("a")), None);
+    }
+
+    #[test]
+    fn finishes_round_when_pages_are_added() {
+        let mut shuffle = Shuffle::parse("7").unwrap();
+        let mut seen = walk(&mut shuffle, SLUGS, 2);
+
+        // no page repeats until all pages known at the start were seen
+        let grown = ["a", "b", "c", "d", "e", "f"];
+        while !SLUGS.iter().all(|slug| seen.contains(slug)) {
+            let slug = shuffle.next(&grown, None).unwrap();
+            assert!(!seen.contains(&slug));
+            seen.push(slug);
+        }
+    }
+
+    #[test]
+    fn parses_cookie() {
+        assert_eq!(Shuffle::parse("12").unwrap().to_cookie(), "12");
+        assert_eq!(Shuffle::parse("12.34").unwrap().to_cookie(), "12.34");
+        assert!(Shuffle::parse("").is_none());
+        assert!(Shuffle::parse("12.x").is_none());
+        assert!(Shuffle::parse("1.2.3").is_none());
+    }
+
+    #[test]
+    fn names_cookie_by_tag() {
+        assert_eq!(cookie_name(None), "random");
+        assert!(cookie_name(Some("cats")) != cookie_name(Some("dogs")));
+        assert_eq!(cookie_name(Some("cats")), cookie_name(Some("cats")));
+    }
+}
@@ -0,0 +1,44 @@
+use index;
+use index::Index;
+use template::escape;
+
+/// Paths crawlers should not visit.
+const DISALLOWED: &'static [&'static str] = &[
+    "/login",
+    "/login-post",
+    "/admin/",
+    "/resume",
+];
+
+/// Lists permalinks of all published pages, with the publication date as
+/// last modification where the page has one.
+pub fn sitemap(index: &Index, base_url: &str) -> String {
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">");
+
+    for entry in index.entries(false) {
+        xml.push_str("<url>");
+        xml.push_str(&format!("<loc>{}{}</loc>", escape(base_url), escape(&entry.url())));
+        if let Some(date) = entry.date {
+            xml.push_str(&format!("<lastmod>{}</lastmod>", index::format_date(date)));
+        }
+        xml.push_str("</url>");
+    }
+
+    xml.push_str("</urlset>");
+    xml
+}
+
+pub fn robots(base_url: &str) -> String {
+    let mut txt = String::new();
+
+    txt.push_str("User-agent: *\n");
+    for path in DISALLOWED {
+        txt.push_str(&format!("Disallow: {}\n", path));
+    }
+    txt.push_str(&format!("\nSitemap: {}/sitemap.xml\n", base_url));
+
+    txt
+}
@@ -25,6 +25,27 @@ fn get_replacement<'g>(sequence: &[u8], globals: &'g Globals) -> &'g [u8] {
     }
 }
 
+/// Escapes text for use inside HTML element or quoted attribute.
+pub fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            '\'' => result.push_str("&#39;"),
+            other => result.push(other),
+        }
+    }
+    result
+}
+
+/// Renders partial template for inclusion into another one.
+pub fn partial(file: &str, globals: &Globals) -> String {
+    String::from_utf8_lossy(&parse(file, globals)).into_owned()
+}
+
 pub fn parse(file: &str, globals: &Globals) -> Vec<u8> {
     let path = Path::new(file);
     let display = path.display();
@@ -99,3 +120,25 @@ pub fn parse(file: &str, globals: &Globals) -> Vec<u8> {
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::escape;
+
+    #[test]
+    fn escapes_markup_and_quotes() {
+        assert_eq!(escape("<a href=\"x\">Tom & 'Jerry'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; &#39;Jerry&#39;&lt;/a&gt;");
+    }
+
+    #[test]
+    fn keeps_plain_text() {
+        assert_eq!(escape(""), "");
+        assert_eq!(escape("Plain text, ünicode"), "Plain text, ünicode");
+    }
+
+    #[test]
+    fn escapes_existing_entities_again() {
+        assert_eq!(escape("&amp;"), "&amp;amp;");
+    }
+}
@@ -1,37 +1,19 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::Read;
-use serde_json;
+use std::path::Path;
 
 use users_models::UserRepr;
+use errors;
+use errors::LoadError;
 
 pub struct Users {
     users: HashMap<String, UserRepr>,
 }
 
 impl Users {
-    pub fn from_file(path: &str) -> Option<Users> {
-        let mut f = match File::open(path) {
-            Ok(f) => f,
-            _ => {
-                println!("failed to open index storage");
-                return None;
-            },
-        };
+    pub fn from_file(path: &Path) -> Result<Users, LoadError> {
+        let deserialized: Vec<UserRepr> = try!(errors::read_json(path));
 
-        let mut contents = String::new();
-        if let Err(e) = f.read_to_string(&mut contents) {
-            panic!("error reading index storage {:?}", e);
-        }
-
-        let deserialized: Vec<UserRepr> = match serde_json::from_str(&contents) {
-            Ok(contents) => contents,
-            Err(e) => {
-                panic!("error deserializing index storage {:?}", e);
-            }
-        };
-
-        Some(Users {
+        Ok(Users {
             users: deserialized.into_iter().map(|v| (v.username.clone(), v)).collect()
         })
     }
@@ -0,0 +1,62 @@
+use std::fs;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex };
+use std::thread;
+use std::time::{ Duration, SystemTime };
+
+use index::Index;
+
+fn modified(path: &Path) -> Option<(SystemTime, u64)> {
+    match fs::metadata(path) {
+        Ok(md) => match md.modified() {
+            Ok(time) => Some((time, md.len())),
+            Err(_) => None,
+        },
+        Err(_) => None,
+    }
+}
+
+/// Polls index file for changes and swaps the freshly parsed index in.
+///
+/// If the changed file fails to load, the error is logged and the last
+/// good index keeps serving until the file changes again. If the file
+/// changes while it is being loaded, for example by an admin edit, the
+/// loaded copy is dropped and the file is loaded again on the next poll.
+pub fn watch(index: Arc<Mutex<Index>>, interval: Duration) -> thread::JoinHandle<()> {
+    let path: PathBuf = match index.lock() {
+        Ok(index) => index.path().into(),
+        Err(e) => panic!("error locking index: {:?}", e),
+    };
+
+    thread::spawn(move || {
+        let mut last_modified = modified(&path);
+
+        loop {
+            thread::sleep(interval);
+
+            let current_modified = modified(&path);
+            if current_modified == last_modified {
+                continue;
+            }
+            last_modified = current_modified;
+
+            if let None = current_modified {
+                println!("index file {:?} is gone, keeping the last loaded index", path);
+                continue;
+            }
+
+            if let Some(reloaded) = Index::load(&path) {
+                match index.lock() {
+                    Ok(_) if modified(&path) != current_modified => {
+                        println!("index {:?} changed while reloading, loading it again", path);
+                    },
+                    Ok(mut index) => {
+                        *index = reloaded;
+                        println!("reloaded index {:?}", path);
+                    },
+                    Err(e) => println!("Error locking index: {:?}", e),
+                }
+            }
+        }
+    })
+}